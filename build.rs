@@ -1,5 +1,5 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("./Protobufs/sandbox.proto")?;
+    tonic_build::compile_protos("./proto/sandbox.proto")?;
 
     Ok(())
 }
@@ -6,13 +6,23 @@
 #![deny(unused_import_braces)]
 #![deny(unused_lifetimes)]
 
-use std::path::Path;
+use std::{path::Path, sync::Arc, time::Duration};
 
 use redis::Commands;
 use serde::Deserialize;
 use service::Service;
 
-use crate::service::MiddlewareManager;
+use crate::service::{
+    settings::Settings,
+    state_store::{memory::MemoryStateStore, redis::RedisStateStore, Store},
+    tls::TlsSettings,
+    websocket::{
+        auth::Authenticator,
+        client::{nickname::NicknameFilter, rate_limit::RateLimits},
+        schema::protocol_schema,
+    },
+    MiddlewareManager,
+};
 
 #[macro_use]
 extern crate log;
@@ -22,9 +32,77 @@ mod service;
 
 #[derive(Deserialize, Debug)]
 struct Config {
+    #[serde(default = "default_address")]
     address: String,
+    #[serde(default = "default_port")]
     port: u16,
+    #[serde(default = "default_redis_addr")]
     redis_addr: String,
+
+    /// Comma separated list of words that are not allowed in nicknames
+    #[serde(default)]
+    nickname_deny_list: Vec<String>,
+
+    /// Share verdicts of identical submissions with other shards through redis
+    #[serde(default)]
+    share_verdict_cache: bool,
+
+    /// Comma separated list of per connection rate limits, e.g. "Compile=5/30,Create=3/60"
+    /// allows five compilations every thirty seconds and three created games every minute
+    #[serde(default)]
+    rate_limits: Vec<String>,
+
+    /// Largest message accepted from clients, in bytes
+    #[serde(default = "default_max_message_size")]
+    max_message_size: usize,
+
+    /// Comma separated list of origins allowed to connect, every origin is allowed if empty
+    #[serde(default)]
+    allowed_origins: Vec<String>,
+
+    /// Key of the HS256 bearer tokens, clients are not authenticated if unset
+    #[serde(default)]
+    auth_token_secret: Option<String>,
+
+    /// Reject clients without a bearer token, requires `auth_token_secret`
+    #[serde(default)]
+    require_auth: bool,
+
+    /// PEM encoded certificate chain, the listener serves wss:// if set together with `tls_key_path`
+    #[serde(default)]
+    tls_cert_path: Option<String>,
+
+    /// PEM encoded private key of the certificate, reloaded together with it on SIGHUP
+    #[serde(default)]
+    tls_key_path: Option<String>,
+
+    /// Seconds clients are given to disconnect after SIGTERM or SIGINT
+    #[serde(default = "default_drain_secs")]
+    drain_secs: u64,
+
+    /// Keep games and sockets in memory instead of redis, only for a single shard
+    #[serde(default)]
+    in_memory_state: bool,
+}
+
+fn default_address() -> String {
+    "0.0.0.0".into()
+}
+
+fn default_port() -> u16 {
+    50000
+}
+
+fn default_redis_addr() -> String {
+    "redis://127.0.0.1:35374".into()
+}
+
+fn default_max_message_size() -> usize {
+    64 * 1024
+}
+
+fn default_drain_secs() -> u64 {
+    10
 }
 
 #[derive(Deserialize, Debug)]
@@ -33,22 +111,21 @@ struct DebugConfig {
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Print the schema of the websocket protocol instead of starting the server
+    if std::env::args().any(|arg| arg == "--dump-schema") {
+        println!("{}", serde_json::to_string_pretty(&protocol_schema())?);
+        return Ok(());
+    }
+
     std::env::set_var("RUST_LOG", "grass");
     env_logger::init();
 
-    // Env config
-    let cfg = match envy::from_env::<Config>() {
-        Ok(config) => config,
-        Err(_) => Config {
-            address: "0.0.0.0".into(),
-            port: 50000,
-            redis_addr: "redis://127.0.0.1:35374".into(),
-        },
-    };
+    // Env config, unset variables fall back to their defaults but invalid values are rejected
+    let cfg = envy::from_env::<Config>()?;
 
-    // Env debug config
+    // Env debug config, there is no redis to reset when the state is kept in memory
     if let Ok(config) = envy::from_env::<DebugConfig>() {
-        if config.should_reset_redis {
+        if config.should_reset_redis && !cfg.in_memory_state {
             let client =
                 redis::Client::open(cfg.redis_addr.to_string()).expect("redis connection failed");
             let mut con = client
@@ -58,6 +135,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    let rate_limits = RateLimits::parse(&cfg.rate_limits)?;
+    let authenticator =
+        Authenticator::new(cfg.allowed_origins, cfg.auth_token_secret, cfg.require_auth)?;
+
+    let tls = match (&cfg.tls_cert_path, &cfg.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            Some(TlsSettings::load(cert_path.into(), key_path.into())?)
+        }
+        (None, None) => None,
+        _ => return Err("TLS requires both tls_cert_path and tls_key_path".into()),
+    };
+
     // Generate random shard (container instance) id
     let shard_id = uuid::Uuid::new_v4().to_string();
     trace!("Generated shard id for initialization: {}", &shard_id);
@@ -66,11 +155,30 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     rt.clone().block_on(async move {
         // Initialize service
         let host_addr = format!("{}:{}", cfg.address, cfg.port);
+        let store: Store = if cfg.in_memory_state {
+            info!("Keeping the state in memory, other shards can not reach this one");
+            Arc::new(MemoryStateStore::default())
+        } else {
+            Arc::new(
+                RedisStateStore::connect(&cfg.redis_addr)
+                    .await
+                    .expect("redis connection failed"),
+            )
+        };
         let mut service = Service::new(
             &shard_id,
             &host_addr,
             Path::new("./tasks.toml"),
-            &cfg.redis_addr,
+            store,
+            Settings {
+                nickname_filter: NicknameFilter::new(&cfg.nickname_deny_list),
+                share_verdict_cache: cfg.share_verdict_cache,
+                rate_limits,
+                max_message_size: cfg.max_message_size,
+                authenticator,
+                tls,
+                drain_period: Duration::from_secs(cfg.drain_secs),
+            },
         )
         .await;
 
@@ -1,6 +1,4 @@
-use r2d2::Pool;
-
-use crate::service::{redis_pool::RedisConnectionManager, Sockets};
+use crate::service::{state_store::Store, Sockets};
 
 /// shard_payload_interceptor
 ///
@@ -8,7 +6,7 @@ use crate::service::{redis_pool::RedisConnectionManager, Sockets};
 pub async fn shard_payload_interceptor(
     _shard_id: String,
     _sockets: Sockets,
-    _redis_pool: Pool<RedisConnectionManager>,
+    _store: Store,
     _payload: (),
 ) {
     // info!(
@@ -27,7 +25,7 @@ pub async fn shard_payload_interceptor(
     //     ShardOpCode::GameEvent => todo!(),
     //     ShardOpCode::Request => {
     //         let request = payload.data::<ShardRequest>();
-    //         match request.handle(shard_id, sockets, redis_pool).await {
+    //         match request.handle(shard_id, sockets, store).await {
     //             Ok(_) => (),
     //             Err(e) => {
     //                 error!("error while handling shard request payload: {}", e);
@@ -36,7 +34,7 @@ pub async fn shard_payload_interceptor(
     //     }
     //     ShardOpCode::Response => {
     //         let response = payload.data::<ShardResponse>();
-    //         match response.handle(shard_id, sockets, redis_pool).await {
+    //         match response.handle(shard_id, sockets, store).await {
     //             Ok(_) => (),
     //             Err(e) => {
     //                 error!("error while handling shard response payload: {}", e);
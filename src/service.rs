@@ -1,29 +1,45 @@
-use std::{path::Path, sync::Arc};
+use std::{path::Path, sync::Arc, time::Duration};
 
 use dashmap::DashMap;
 use futures::{
     channel::oneshot::{Receiver, Sender},
-    Future,
+    Future, StreamExt,
+};
+use tokio::{
+    net::TcpListener,
+    signal::unix::{signal, SignalKind},
 };
-use r2d2::Pool;
-use redis::{ControlFlow, PubSubCommands};
-use tokio::net::TcpListener;
 use uuid::Uuid;
 
 use self::{
     error::CriticalError,
-    redis_pool::RedisConnectionManager,
-    websocket::client::{game::task::GameTask, SocketClient},
+    settings::Settings,
+    state_store::Store,
+    tls::TlsSettings,
+    websocket::{
+        client::{game::task::GameTask, SocketClient},
+        rejections::{RejectionReason, HANDSHAKE_REJECTIONS},
+    },
 };
 
 pub mod error;
 pub mod extended_select;
 pub mod redis_pool;
+pub mod settings;
+pub mod shutdown;
+pub mod state_store;
 pub mod task_loader;
+pub mod tls;
 pub mod websocket;
 
 pub type Sockets = Arc<DashMap<Uuid, SocketClient>>;
 
+/// Time a client has to finish the TLS handshake, so stalled handshakes do not pile up
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause after a failed accept, e.g. when the shard ran out of file descriptors
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
 /*
     # Flow chart of the websocket part of this service
 
@@ -39,7 +55,7 @@ pub type Sockets = Arc<DashMap<Uuid, SocketClient>>;
     └────► on received from other sharding ────► send to middleware
 */
 
-type ShardingMiddleware<F> = fn(String, Sockets, Pool<RedisConnectionManager>, ()) -> F;
+type ShardingMiddleware<F> = fn(String, Sockets, Store, ()) -> F;
 
 pub struct MiddlewareManager<F>
 where
@@ -72,17 +88,19 @@ pub struct Service<'a> {
     // shard enviromental variables
     shard_id: &'a str,
     host_addr: &'a str,
-    redis_addr: &'a str,
 
     // List of open socket connections
     connections: Sockets,
 
-    // Redis connection pool
-    redis_pool: Pool<RedisConnectionManager>,
+    // Games and sockets shared with other shards
+    store: Store,
 
     // Available tasks
     available_tasks: Arc<Vec<GameTask>>,
 
+    // Settings shared by all connections
+    settings: Arc<Settings>,
+
     // Error channel to trigger shutdown of service if something goes wrong
     error_channel: (
         Option<Sender<CriticalError>>,
@@ -95,12 +113,9 @@ impl<'a> Service<'a> {
         shard_id: &'a str,
         host_addr: &'a str,
         game_loading_path: &Path,
-        redis_addr: &'a str,
+        store: Store,
+        settings: Settings,
     ) -> Service<'a> {
-        // Create redis connection poool
-        let manager = RedisConnectionManager::new(redis_addr).unwrap();
-        let redis_pool = r2d2::Pool::builder().build(manager).unwrap();
-
         // Initialize thread channel to handle critical errors that may occur inside the application
         let (error_tx, error_rx) = futures::channel::oneshot::channel::<CriticalError>();
 
@@ -112,12 +127,12 @@ impl<'a> Service<'a> {
         Self {
             shard_id,
             host_addr,
-            redis_addr,
 
             connections: Arc::new(DashMap::new()),
-            redis_pool,
+            store,
 
             available_tasks: Arc::new(tasks),
+            settings: Arc::new(settings),
 
             error_channel: (Some(error_tx), Some(error_rx)),
         }
@@ -133,7 +148,7 @@ impl<'a> Service<'a> {
         superluminal_perf::begin_event_with_color("Service runner", 0x3ca358);
 
         // Create a seperate thread for WebSockets
-        let redis_pool = self.redis_pool.clone();
+        let store = self.store.clone();
         let socket_connections = self.connections.clone();
         let shard_id = self.shard_id.to_string();
         let host_addr = self.host_addr.to_string();
@@ -141,83 +156,157 @@ impl<'a> Service<'a> {
         // Spawns websocket server task which handles all incoming tokio-tungstenite connections
         // And redirects them into the function websocket::accept_connection(...)
         // TCP system works with tokio-tungstenite through tokios TcpListener
-        let pool = redis_pool.clone();
+        let pool = store.clone();
         let available_tasks = self.available_tasks.clone();
+        let settings = self.settings.clone();
+
+        // Reload the certificate on SIGHUP, the listener keeps the old one if the new one is invalid
+        if settings.tls.is_some() {
+            match signal(SignalKind::hangup()) {
+                Ok(mut hangup) => {
+                    let settings = settings.clone();
+                    tokio::spawn(async move {
+                        while hangup.recv().await.is_some() {
+                            if let Some(tls) = &settings.tls {
+                                match tls.reload() {
+                                    Ok(_) => info!("Reloaded TLS certificate"),
+                                    Err(e) => error!("Could not reload TLS certificate: {}", e),
+                                }
+                            }
+                        }
+                    });
+                }
+                Err(e) => error!(
+                    "Could not listen for SIGHUP, the TLS certificate will not be reloaded: {}",
+                    e
+                ),
+            }
+        }
+
         let joinhandle_ws = tokio::spawn(async move {
             superluminal_perf::begin_event_with_color("Websocket server", 0x3f7ea6);
             trace!("Launching socket shard");
             let try_socket = TcpListener::bind(&host_addr).await;
             let listener = try_socket.expect("Failed to bind");
-            info!("Socket shard listening on: {}", host_addr);
-
-            while let Ok((stream, _)) = listener.accept().await {
+            info!(
+                "Socket shard listening on: {} ({})",
+                host_addr,
+                if settings.tls.is_some() { "wss" } else { "ws" }
+            );
+
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        error!("Could not accept a connection: {}", e);
+                        tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                        continue;
+                    }
+                };
                 let available_tasks = available_tasks.clone();
+                let settings = settings.clone();
+                let pool = pool.clone();
+                let socket_connections = socket_connections.clone();
+                let shard_id = shard_id.to_string();
                 superluminal_perf::begin_event("accept connection");
-                tokio::spawn(websocket::accept_connection(
-                    stream,
-                    available_tasks,
-                    pool.clone(),
-                    socket_connections.clone(),
-                    shard_id.to_string(),
-                ));
+                tokio::spawn(async move {
+                    // The TLS handshake runs inside the connection task to not block the listener
+                    match settings.tls.as_ref().map(TlsSettings::acceptor) {
+                        Some(acceptor) => match tokio::time::timeout(
+                            TLS_HANDSHAKE_TIMEOUT,
+                            acceptor.accept(stream),
+                        )
+                        .await
+                        {
+                            Ok(Ok(stream)) => {
+                                websocket::accept_connection(
+                                    stream,
+                                    addr,
+                                    available_tasks,
+                                    settings,
+                                    pool,
+                                    socket_connections,
+                                    shard_id,
+                                )
+                                .await
+                            }
+                            Ok(Err(e)) => {
+                                let count = HANDSHAKE_REJECTIONS.record(RejectionReason::Tls);
+                                warn!(
+                                    "Rejected TLS handshake with {} ({} so far): {}",
+                                    addr, count, e
+                                );
+                            }
+                            Err(_) => {
+                                let count = HANDSHAKE_REJECTIONS.record(RejectionReason::Tls);
+                                warn!(
+                                    "Rejected TLS handshake with {} ({} so far): timed out",
+                                    addr, count
+                                );
+                            }
+                        },
+                        None => {
+                            websocket::accept_connection(
+                                stream,
+                                addr,
+                                available_tasks,
+                                settings,
+                                pool,
+                                socket_connections,
+                                shard_id,
+                            )
+                            .await
+                        }
+                    }
+                });
                 superluminal_perf::end_event();
             }
-            superluminal_perf::end_event();
         });
 
-        // Clone arcs and get ip addresses for redis to send into redis reader task
+        // Clone arcs to send into the pub/sub reader task
         let shard_id = self.shard_id.to_string();
-        let redis_addr = self.redis_addr.to_string();
         let _socket_connections = self.connections.clone();
 
         // Create a seperate thread for PubSub channels
-        // Spawns the tokio task that handles all incoming messages from redis
-        let _pool = redis_pool.clone();
+        // Spawns the tokio task that handles all incoming messages from the state store
+        let _pool = store.clone();
         let joinhandle_presence = tokio::spawn(async move {
             superluminal_perf::begin_event("Redis pub/sub reader");
 
-            // Opens a new redis connection outside the pool to leverage better connection speeds
-            let client = redis::Client::open(redis_addr).expect("redis connection failed");
-            let mut con = client
-                .get_connection()
-                .expect("could not get redis connection");
-
+            // Subscribe to presence channel and receive messages from other sharding (socket servers)
+            let mut messages = store
+                .subscribe(&shard_id)
+                .await
+                .expect("could not subscribe to the shard channel");
             info!("shard id registered to pub/sub: {}", shard_id);
 
-            // Subscribe to presence channel and receive messages from other sharding (socket servers)
             let _local_shard_id = shard_id.clone();
-            let _: () = con
-                .subscribe(&[shard_id], |_msg| {
-                    // trace!("Receiving message from shard");
-                    // let local_middleware = middleware.clone();
-                    // let local_socket_connections = socket_connections.clone();
-                    // let local_pool = pool.clone();
-                    // let local_shard_id = local_shard_id.clone();
-                    // trace!("Parsing payload from shard message");
-                    // let payload: Vec<u8> = msg
-                    //     .get_payload()
-                    //     .expect("could not get pub/sub message payload");
-                    // trace!(
-                    //     "Payload from shard message has length {} bytes",
-                    //     payload.len()
-                    // );
-
-                    // // Todo: error handling
-                    // let payload = flexbuffers::Reader::get_root(payload.as_slice()).unwrap();
-                    // let model = ShardDefaultModel::deserialize(payload).unwrap();
-                    // trace!("Deserialized payload and found opcode: {:?}", &model.op);
-
-                    // // Call middleware function and pass in the payload
-                    // futures::executor::block_on((local_middleware.function)(
-                    //     local_shard_id,
-                    //     local_socket_connections,
-                    //     local_pool,
-                    //     model,
-                    // ));
-
-                    ControlFlow::Continue
-                })
-                .unwrap();
+            while let Some(_payload) = messages.next().await {
+                // trace!("Receiving message from shard");
+                // let local_middleware = middleware.clone();
+                // let local_socket_connections = socket_connections.clone();
+                // let local_pool = pool.clone();
+                // let local_shard_id = local_shard_id.clone();
+                // trace!("Parsing payload from shard message");
+                // trace!(
+                //     "Payload from shard message has length {} bytes",
+                //     payload.len()
+                // );
+
+                // // Todo: error handling
+                // let payload = flexbuffers::Reader::get_root(payload.as_slice()).unwrap();
+                // let model = ShardDefaultModel::deserialize(payload).unwrap();
+                // trace!("Deserialized payload and found opcode: {:?}", &model.op);
+
+                // // Call middleware function and pass in the payload
+                // (local_middleware.function)(
+                //     local_shard_id,
+                //     local_socket_connections,
+                //     local_pool,
+                //     model,
+                // )
+                // .await;
+            }
             superluminal_perf::end_event();
         });
 
@@ -226,12 +315,22 @@ impl<'a> Service<'a> {
         // This custom select function exits when one of the futures returns,
         // In case of a critical error the service will exit (error_rx)
         // and the option it returns will contain the CriticalError
-        let critical_error = extended_select::select(
-            joinhandle_ws,
-            joinhandle_presence,
-            self.error_channel.1.take().unwrap(),
-        )
-        .await;
+        let mut joinhandle_ws = joinhandle_ws;
+        let critical_error = tokio::select! {
+            critical_error = extended_select::select(
+                &mut joinhandle_ws,
+                joinhandle_presence,
+                self.error_channel.1.take().unwrap(),
+            ) => critical_error,
+            _ = shutdown::signal() => {
+                // Stop accepting connections before draining the open ones
+                joinhandle_ws.abort();
+                shutdown::drain(&self.connections, &self.store, self.settings.drain_period)
+                    .await;
+                info!("Shard shut down");
+                return Ok(());
+            }
+        };
         match critical_error {
             Some(error) => {
                 match error {
@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+use super::{
+    tls::TlsSettings,
+    websocket::{
+        auth::Authenticator,
+        client::{nickname::NicknameFilter, rate_limit::RateLimits},
+    },
+};
+
+/// Settings shared by every connection on this shard
+#[derive(Debug)]
+pub struct Settings {
+    /// Nickname validation rules
+    pub(crate) nickname_filter: NicknameFilter,
+
+    /// If verdicts of submissions are shared with other shards through redis
+    pub(crate) share_verdict_cache: bool,
+
+    /// Rate limits of request op codes, applied per connection
+    pub(crate) rate_limits: RateLimits,
+
+    /// Largest message accepted from clients, in bytes
+    pub(crate) max_message_size: usize,
+
+    /// Origin and token checks of websocket handshakes
+    pub(crate) authenticator: Authenticator,
+
+    /// Certificate of the listener, None serves plain websockets
+    pub(crate) tls: Option<TlsSettings>,
+
+    /// How long clients are given to disconnect when the shard shuts down
+    pub(crate) drain_period: Duration,
+}
+
+#[cfg(test)]
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            nickname_filter: NicknameFilter::default(),
+            share_verdict_cache: false,
+            rate_limits: RateLimits::default(),
+            max_message_size: 64 << 10,
+            authenticator: Authenticator::new(vec![], None, false).unwrap(),
+            tls: None,
+            drain_period: Duration::from_secs(10),
+        }
+    }
+}
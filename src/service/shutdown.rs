@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use tokio::signal::unix::{signal as unix_signal, SignalKind};
+use tokio_tungstenite::tungstenite::{
+    protocol::{frame::coding::CloseCode, CloseFrame},
+    Message,
+};
+use uuid::Uuid;
+
+use super::{
+    state_store::{Store, StoredGame},
+    websocket::{
+        client::models::{forced_disconnection::ForcedDisconnection, DefaultModel},
+        outbound::Priority,
+    },
+    Sockets,
+};
+
+/// How often the remaining sockets are counted while draining
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Resolves once the shard is asked to stop through SIGTERM or SIGINT
+pub async fn signal() {
+    let mut terminate = unix_signal(SignalKind::terminate()).expect("could not listen for SIGTERM");
+    tokio::select! {
+        _ = terminate.recv() => info!("Received SIGTERM, shutting down"),
+        _ = tokio::signal::ctrl_c() => info!("Received SIGINT, shutting down"),
+    }
+}
+
+/// Ends every game on this shard, removes the shard from the global datastore
+/// and waits at most `drain_period` for the clients to disconnect.
+/// Clients are told to reconnect once the drain period is over, by then the shard is gone
+pub async fn drain(sockets: &Sockets, store: &Store, drain_period: Duration) {
+    superluminal_perf::begin_event("shard shutdown");
+    info!("Draining {} sockets", sockets.len());
+
+    let client_ids = sockets
+        .iter()
+        .map(|client| *client.key())
+        .collect::<Vec<Uuid>>();
+    for client_id in client_ids.iter() {
+        // The game is taken out of the socket while it is shut down, so the socket is not locked
+        // across an await
+        let (client, game) = match sockets.get_mut(client_id) {
+            Some(mut client) => {
+                let game = client.game.take();
+                (client.clone(), game)
+            }
+            None => continue,
+        };
+
+        if let Some(mut game) = game {
+            if let Err(e) = game.shutdown_shard(drain_period).await {
+                error!("Failed to shut down game of {}: {}", client_id, e);
+            }
+
+            // Leaving the game is left to the disconnect of the client
+            match sockets.get_mut(client_id) {
+                Some(mut socket) => socket.game = Some(game),
+                None => game.close().await,
+            }
+        }
+
+        for game_id in client.created_games.iter() {
+            delete_created_game(store, game_id).await;
+        }
+
+        if let Err(e) = client.unregister(store).await {
+            error!("Failed to unregister socket {}: {}", client_id, e);
+        }
+
+        // The client answers the close frame, which ends its connection like any other disconnect
+        let _ = client
+            .send_model(DefaultModel::new(ForcedDisconnection {}))
+            .await;
+        let _ = client.send_channel.push(
+            Message::Close(Some(CloseFrame {
+                code: CloseCode::Away,
+                reason: "shard is shutting down".into(),
+            })),
+            Priority::Normal,
+        );
+    }
+
+    let drained = tokio::time::timeout(drain_period, async {
+        while !sockets.is_empty() {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    })
+    .await;
+    match drained {
+        Ok(_) => info!("All sockets disconnected"),
+        Err(_) => warn!(
+            "{} sockets did not disconnect within {:?}",
+            sockets.len(),
+            drain_period
+        ),
+    }
+    superluminal_perf::end_event();
+}
+
+/// Deletes a game created on this shard that no client joined, hosted games are ended by their host
+async fn delete_created_game(store: &Store, game_id: &str) {
+    match store.lookup_game(game_id).await {
+        Ok(Some(StoredGame::Created)) => {
+            if let Err(e) = store.delete_game(game_id).await {
+                error!("Failed to delete created game {}: {}", game_id, e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => error!("Failed to look up created game {}: {}", game_id, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use dashmap::DashMap;
+
+    use crate::service::{
+        state_store::memory::MemoryStateStore,
+        websocket::{
+            client::{
+                rate_limit::{RateLimiter, RateLimits},
+                SocketClient,
+            },
+            outbound::OutboundQueue,
+            protocol::Protocol,
+        },
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn drain_deletes_games_created_on_the_shard() {
+        let store: Store = Arc::new(MemoryStateStore::default());
+        let sockets: Sockets = Arc::new(DashMap::new());
+
+        let (queue, mut messages) = OutboundQueue::new();
+        let mut client = SocketClient::new(
+            "127.0.0.1:1234".parse().unwrap(),
+            queue,
+            Protocol::DEFAULT,
+            None,
+            RateLimiter::new(&RateLimits::default()),
+        );
+        client.created_games.push("1234567890".to_owned());
+        store.create_game("1234567890").await.unwrap();
+        store.create_game("0987654321").await.unwrap();
+        let client_id = client.id;
+        sockets.insert(client_id, client);
+
+        // The client disconnects once it reads the close frame
+        let disconnect = tokio::spawn({
+            let sockets = sockets.clone();
+            async move {
+                while let Some(message) = messages.recv().await {
+                    if message.is_close() {
+                        sockets.remove(&client_id);
+                    }
+                }
+            }
+        });
+
+        drain(&sockets, &store, Duration::from_secs(5)).await;
+
+        assert!(sockets.is_empty());
+        assert!(store.lookup_game("1234567890").await.unwrap().is_none());
+        assert!(store.lookup_game("0987654321").await.unwrap().is_some());
+        disconnect.await.unwrap();
+    }
+}
@@ -0,0 +1,88 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use uuid::Uuid;
+
+use super::websocket::client::game::redis_game::RedisGame;
+
+pub mod memory;
+pub mod redis;
+
+/// State store shared by every task of the shard
+pub type Store = Arc<dyn StateStore>;
+
+/// Game entry of the state store
+#[derive(Debug, Clone)]
+pub enum StoredGame {
+    /// Created through a create request, the first client to join becomes the host
+    Created,
+
+    /// Hosted by a client on the shard of the entry
+    Hosted(RedisGame),
+}
+
+/// State shared between shards, which sockets are connected where and which games exist.
+/// Redis is used when running several shards, a single shard can keep the state in memory
+#[async_trait]
+pub trait StateStore: fmt::Debug + Send + Sync {
+    /// Records the shard a socket is connected to
+    async fn register_socket(&self, client_id: &Uuid, shard_id: &str) -> Result<(), StateError>;
+
+    async fn unregister_socket(&self, client_id: &Uuid) -> Result<(), StateError>;
+
+    /// Reserves a game id without a host
+    async fn create_game(&self, game_id: &str) -> Result<(), StateError>;
+
+    /// Stores the host of a game, replacing any previous entry
+    async fn host_game(&self, game_id: &str, game: &RedisGame) -> Result<(), StateError>;
+
+    async fn lookup_game(&self, game_id: &str) -> Result<Option<StoredGame>, StateError>;
+
+    async fn delete_game(&self, game_id: &str) -> Result<(), StateError>;
+
+    /// Fetches the serialized verdict of a submission hash
+    async fn cached_verdict(&self, submission_hash: &str) -> Result<Option<String>, StateError>;
+
+    /// Stores the serialized verdict of a submission hash, it expires after `ttl`
+    async fn cache_verdict(
+        &self,
+        submission_hash: &str,
+        verdict: String,
+        ttl: Duration,
+    ) -> Result<(), StateError>;
+
+    /// Payloads published to the shard from now on, the stream ends if the store goes away
+    async fn subscribe(&self, shard_id: &str) -> Result<BoxStream<'static, Vec<u8>>, StateError>;
+}
+
+#[derive(Debug)]
+pub enum StateError {
+    Redis(::redis::RedisError),
+
+    /// A stored value could not be parsed
+    Corrupt(serde_json::Error),
+}
+
+impl From<::redis::RedisError> for StateError {
+    fn from(e: ::redis::RedisError) -> Self {
+        StateError::Redis(e)
+    }
+}
+
+impl From<serde_json::Error> for StateError {
+    fn from(e: serde_json::Error) -> Self {
+        StateError::Corrupt(e)
+    }
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Redis(e) => write!(f, "redis error: {}", e),
+            StateError::Corrupt(e) => write!(f, "corrupt state: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
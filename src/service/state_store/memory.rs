@@ -0,0 +1,205 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use uuid::Uuid;
+
+use crate::service::websocket::client::game::redis_game::RedisGame;
+
+use super::{StateError, StateStore, StoredGame};
+
+/// Payloads a subscriber can fall behind by before it misses some
+const CHANNEL_CAPACITY: usize = 256;
+
+/// State store that lives inside the shard, for running a single shard without a redis server
+#[derive(Debug, Default)]
+pub struct MemoryStateStore {
+    sockets: DashMap<Uuid, String>,
+    games: DashMap<String, StoredGame>,
+
+    /// Verdicts with the moment they expire
+    verdicts: DashMap<String, (String, Instant)>,
+    channels: DashMap<String, broadcast::Sender<Vec<u8>>>,
+}
+
+impl MemoryStateStore {
+    fn channel(&self, shard_id: &str) -> broadcast::Sender<Vec<u8>> {
+        self.channels
+            .entry(shard_id.to_owned())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .clone()
+    }
+}
+
+#[async_trait]
+impl StateStore for MemoryStateStore {
+    async fn register_socket(&self, client_id: &Uuid, shard_id: &str) -> Result<(), StateError> {
+        self.sockets.insert(*client_id, shard_id.to_owned());
+        Ok(())
+    }
+
+    async fn unregister_socket(&self, client_id: &Uuid) -> Result<(), StateError> {
+        self.sockets.remove(client_id);
+        Ok(())
+    }
+
+    async fn create_game(&self, game_id: &str) -> Result<(), StateError> {
+        self.games.insert(game_id.to_owned(), StoredGame::Created);
+        Ok(())
+    }
+
+    async fn host_game(&self, game_id: &str, game: &RedisGame) -> Result<(), StateError> {
+        self.games
+            .insert(game_id.to_owned(), StoredGame::Hosted(game.to_owned()));
+        Ok(())
+    }
+
+    async fn lookup_game(&self, game_id: &str) -> Result<Option<StoredGame>, StateError> {
+        Ok(self.games.get(game_id).map(|game| game.value().to_owned()))
+    }
+
+    async fn delete_game(&self, game_id: &str) -> Result<(), StateError> {
+        self.games.remove(game_id);
+        Ok(())
+    }
+
+    async fn cached_verdict(&self, submission_hash: &str) -> Result<Option<String>, StateError> {
+        // Expired verdicts are removed once they are looked up
+        let verdict = self
+            .verdicts
+            .get(submission_hash)
+            .map(|entry| entry.value().to_owned());
+        match verdict {
+            Some((verdict, expires_at)) if expires_at > Instant::now() => Ok(Some(verdict)),
+            Some(_) => {
+                self.verdicts.remove(submission_hash);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn cache_verdict(
+        &self,
+        submission_hash: &str,
+        verdict: String,
+        ttl: Duration,
+    ) -> Result<(), StateError> {
+        self.verdicts
+            .insert(submission_hash.to_owned(), (verdict, Instant::now() + ttl));
+        Ok(())
+    }
+
+    async fn subscribe(&self, shard_id: &str) -> Result<BoxStream<'static, Vec<u8>>, StateError> {
+        let receiver = self.channel(shard_id).subscribe();
+        Ok(stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(payload) => return Some((payload, receiver)),
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Subscriber fell behind and missed {} payloads", missed);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+
+    /// Publishes a payload the way redis does, it is dropped if nobody is subscribed
+    fn publish(store: &MemoryStateStore, shard_id: &str, payload: u8) {
+        let _ = store.channel(shard_id).send(vec![payload]);
+    }
+
+    #[tokio::test]
+    async fn games_are_created_hosted_and_deleted() {
+        let store = MemoryStateStore::default();
+        assert!(store.lookup_game("1").await.unwrap().is_none());
+
+        store.create_game("1").await.unwrap();
+        assert!(matches!(
+            store.lookup_game("1").await.unwrap(),
+            Some(StoredGame::Created)
+        ));
+
+        let host_id = Uuid::new_v4();
+        let game = RedisGame {
+            shard_id: "shard".to_owned(),
+            host_id,
+        };
+        store.host_game("1", &game).await.unwrap();
+        match store.lookup_game("1").await.unwrap() {
+            Some(StoredGame::Hosted(game)) => {
+                assert_eq!(game.shard_id, "shard");
+                assert_eq!(game.host_id, host_id);
+            }
+            game => panic!("game was not hosted: {:?}", game),
+        }
+
+        store.delete_game("1").await.unwrap();
+        assert!(store.lookup_game("1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn verdicts_expire_after_their_ttl() {
+        let store = MemoryStateStore::default();
+        store
+            .cache_verdict("kept", "Accepted".to_owned(), Duration::from_secs(3600))
+            .await
+            .unwrap();
+        store
+            .cache_verdict("expired", "WrongAnswer".to_owned(), Duration::ZERO)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.cached_verdict("kept").await.unwrap().as_deref(),
+            Some("Accepted")
+        );
+        assert_eq!(store.cached_verdict("expired").await.unwrap(), None);
+        assert!(!store.verdicts.contains_key("expired"));
+        assert_eq!(store.cached_verdict("unknown").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn payloads_reach_the_subscribers_of_a_shard() {
+        let store = MemoryStateStore::default();
+
+        // Payloads published before anyone subscribed are dropped
+        publish(&store, "shard", 0);
+
+        let mut first = store.subscribe("shard").await.unwrap();
+        let mut second = store.subscribe("shard").await.unwrap();
+        let mut other = store.subscribe("other").await.unwrap();
+        publish(&store, "shard", 1);
+        publish(&store, "shard", 2);
+
+        assert_eq!(first.next().await, Some(vec![1]));
+        assert_eq!(first.next().await, Some(vec![2]));
+        assert_eq!(second.next().await, Some(vec![1]));
+        assert!(other.next().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn lagging_subscribers_skip_missed_payloads() {
+        let store = MemoryStateStore::default();
+        let mut subscriber = store.subscribe("shard").await.unwrap();
+        for payload in 0..=CHANNEL_CAPACITY {
+            publish(&store, "shard", payload as u8);
+        }
+
+        assert_eq!(subscriber.next().await, Some(vec![1]));
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
+use redis::{AsyncCommands, RedisResult};
+use uuid::Uuid;
+
+use crate::service::{redis_pool::RedisPool, websocket::client::game::redis_game::RedisGame};
+
+use super::{StateError, StateStore, StoredGame};
+
+/// State store of shards sharing a redis server
+#[derive(Debug)]
+pub struct RedisStateStore {
+    /// Used to open subscriptions, a subscribed connection can not send commands
+    client: redis::Client,
+    redis_pool: RedisPool,
+}
+
+impl RedisStateStore {
+    pub async fn connect(redis_addr: &str) -> RedisResult<RedisStateStore> {
+        Ok(RedisStateStore {
+            client: redis::Client::open(redis_addr)?,
+            redis_pool: RedisPool::connect(redis_addr).await?,
+        })
+    }
+}
+
+#[async_trait]
+impl StateStore for RedisStateStore {
+    async fn register_socket(&self, client_id: &Uuid, shard_id: &str) -> Result<(), StateError> {
+        let mut conn = self.redis_pool.get();
+        let _: () = conn
+            .set(format!("SOCKET:USER:{}", client_id), shard_id)
+            .await?;
+        Ok(())
+    }
+
+    async fn unregister_socket(&self, client_id: &Uuid) -> Result<(), StateError> {
+        let mut conn = self.redis_pool.get();
+        let _: () = conn.del(format!("SOCKET:USER:{}", client_id)).await?;
+        Ok(())
+    }
+
+    async fn create_game(&self, game_id: &str) -> Result<(), StateError> {
+        // An empty value marks a game without a host
+        let mut conn = self.redis_pool.get();
+        let _: () = conn.set(format!("GAME:{}", game_id), "").await?;
+        Ok(())
+    }
+
+    async fn host_game(&self, game_id: &str, game: &RedisGame) -> Result<(), StateError> {
+        let mut conn = self.redis_pool.get();
+        let _: () = conn
+            .set(format!("GAME:{}", game_id), serde_json::to_string(game)?)
+            .await?;
+        Ok(())
+    }
+
+    async fn lookup_game(&self, game_id: &str) -> Result<Option<StoredGame>, StateError> {
+        let mut conn = self.redis_pool.get();
+        let game: Option<String> = conn.get(format!("GAME:{}", game_id)).await?;
+        Ok(match game {
+            Some(game) if game.is_empty() => Some(StoredGame::Created),
+            Some(game) => Some(StoredGame::Hosted(serde_json::from_str(&game)?)),
+            None => None,
+        })
+    }
+
+    async fn delete_game(&self, game_id: &str) -> Result<(), StateError> {
+        let mut conn = self.redis_pool.get();
+        let _: () = conn.del(format!("GAME:{}", game_id)).await?;
+        Ok(())
+    }
+
+    async fn cached_verdict(&self, submission_hash: &str) -> Result<Option<String>, StateError> {
+        let mut conn = self.redis_pool.get();
+        Ok(conn.get(format!("VERDICT:{}", submission_hash)).await?)
+    }
+
+    async fn cache_verdict(
+        &self,
+        submission_hash: &str,
+        verdict: String,
+        ttl: Duration,
+    ) -> Result<(), StateError> {
+        let mut conn = self.redis_pool.get();
+        let _: () = conn
+            .set_ex(
+                format!("VERDICT:{}", submission_hash),
+                verdict,
+                ttl.as_secs() as usize,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn subscribe(&self, shard_id: &str) -> Result<BoxStream<'static, Vec<u8>>, StateError> {
+        let mut pubsub = self.client.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe(shard_id).await?;
+        Ok(pubsub
+            .into_on_message()
+            .filter_map(|message| async move { message.get_payload::<Vec<u8>>().ok() })
+            .boxed())
+    }
+}
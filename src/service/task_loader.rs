@@ -1,13 +1,70 @@
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
 use super::websocket::client::game::task::GameTask;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Deserialize)]
 pub struct FileTaskList {
     tasks: Vec<GameTask>,
 }
 
 pub fn load_tasks(toml: &str) -> Vec<GameTask> {
     let tasks: FileTaskList = toml::from_str(toml).unwrap();
+    for task in &tasks.tasks {
+        if let Err(e) = task.validate() {
+            panic!("Invalid task {}: {}", task.task_id, e);
+        }
+    }
+
     tasks.tasks
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_tasks_are_valid() {
+        assert!(!load_tasks(include_str!("../../tasks.toml")).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "sample test 1 is not a public test case")]
+    fn private_samples_are_rejected() {
+        load_tasks(
+            r#"
+            [[tasks]]
+            task_id = "248fa5e0-a3ac-4de0-a077-307c80d12126"
+            statement = { body = "Echo", sample_test_ids = [1] }
+
+            [[tasks.public_test_cases]]
+            stdin = "a"
+            expected = "a"
+            id = 0
+
+            [[tasks.private_test_cases]]
+            stdin = "b"
+            expected = "b"
+            id = 1
+            "#,
+        );
+    }
+
+    #[test]
+    fn statements_fall_back_to_the_language_and_default() {
+        let tasks = load_tasks(
+            r#"
+            [[tasks]]
+            task_id = "248fa5e0-a3ac-4de0-a077-307c80d12126"
+            statement = { body = "Echo" }
+            localized_statements = { sv = { body = "Eko" } }
+            public_test_cases = []
+            private_test_cases = []
+            "#,
+        );
+
+        assert_eq!(tasks[0].statement(Some("sv-SE")).body, "Eko");
+        assert_eq!(tasks[0].statement(Some("sv")).body, "Eko");
+        assert_eq!(tasks[0].statement(Some("de")).body, "Echo");
+        assert_eq!(tasks[0].statement(None).body, "Echo");
+    }
+}
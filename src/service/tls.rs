@@ -0,0 +1,123 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use tokio_rustls::{
+    rustls::{self, Certificate, PrivateKey, ServerConfig},
+    TlsAcceptor,
+};
+
+/// Certificate and key the websocket listener terminates TLS with,
+/// both files are read again on reload so certificates can be renewed without a restart
+pub struct TlsSettings {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    acceptor: RwLock<TlsAcceptor>,
+}
+
+impl TlsSettings {
+    pub fn load(cert_path: PathBuf, key_path: PathBuf) -> Result<TlsSettings, TlsError> {
+        let acceptor = Self::build_acceptor(&cert_path, &key_path)?;
+        Ok(TlsSettings {
+            cert_path,
+            key_path,
+            acceptor: RwLock::new(acceptor),
+        })
+    }
+
+    /// Acceptor with the most recently loaded certificate
+    pub fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor
+            .read()
+            .expect("tls acceptor lock should not be poisoned")
+            .clone()
+    }
+
+    /// Reads the certificate and key again, the current ones are kept if the new ones are invalid.
+    /// Connections that are already established keep using the certificate they were accepted with
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let acceptor = Self::build_acceptor(&self.cert_path, &self.key_path)?;
+        *self
+            .acceptor
+            .write()
+            .expect("tls acceptor lock should not be poisoned") = acceptor;
+        Ok(())
+    }
+
+    fn build_acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor, TlsError> {
+        let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))?
+            .into_iter()
+            .map(Certificate)
+            .collect::<Vec<Certificate>>();
+        if certs.is_empty() {
+            return Err(TlsError::NoCertificate);
+        }
+
+        let key = rustls_pemfile::read_all(&mut BufReader::new(File::open(key_path)?))?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::RSAKey(key)
+                | rustls_pemfile::Item::PKCS8Key(key)
+                | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+                _ => None,
+            })
+            .ok_or(TlsError::NoPrivateKey)?;
+
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+impl fmt::Debug for TlsSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsSettings")
+            .field("cert_path", &self.cert_path)
+            .field("key_path", &self.key_path)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(io::Error),
+    Rustls(rustls::Error),
+
+    /// The certificate file does not contain any PEM encoded certificate
+    NoCertificate,
+
+    /// The key file does not contain a PEM encoded RSA, PKCS8 or EC private key
+    NoPrivateKey,
+}
+
+impl From<io::Error> for TlsError {
+    fn from(e: io::Error) -> Self {
+        TlsError::Io(e)
+    }
+}
+
+impl From<rustls::Error> for TlsError {
+    fn from(e: rustls::Error) -> Self {
+        TlsError::Rustls(e)
+    }
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::Io(e) => write!(f, "could not read certificate or key: {}", e),
+            TlsError::Rustls(e) => write!(f, "invalid certificate or key: {}", e),
+            TlsError::NoCertificate => write!(f, "no certificate found in the certificate file"),
+            TlsError::NoPrivateKey => write!(f, "no private key found in the key file"),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
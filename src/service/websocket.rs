@@ -1,73 +1,185 @@
-use std::sync::Arc;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use futures::{future, SinkExt, StreamExt, TryStreamExt};
-use r2d2::Pool;
-use tokio::{net::TcpStream, sync::mpsc::Sender};
-use tokio_tungstenite::tungstenite::Message;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue, StatusCode},
+    protocol::WebSocketConfig,
+    Message,
+};
 
-use self::client::{game::task::GameTask, SocketClient};
+use self::{
+    client::{game::task::GameTask, rate_limit::RateLimiter, SocketClient},
+    outbound::OutboundQueue,
+    protocol::Protocol,
+    rejections::{RejectionReason, HANDSHAKE_REJECTIONS},
+};
 
-use super::{redis_pool::RedisConnectionManager, Sockets};
+use super::{settings::Settings, state_store::Store, Sockets};
 
+pub mod auth;
 pub mod client;
+pub mod outbound;
+pub mod protocol;
+pub mod rejections;
+pub mod schema;
 
-type SocketSender = Sender<Message>;
+type SocketSender = OutboundQueue;
 
-pub async fn accept_connection(
-    stream: TcpStream,
+/// How often the server pings every socket
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Sockets that have not sent anything for this many heartbeats are closed
+const MAX_MISSED_HEARTBEATS: u32 = 3;
+
+/// Tracks whether the peer of a socket is still alive. Messages are handled one at a time, so a
+/// client can not answer pings while one of its requests runs and is never idle during that time
+#[derive(Debug, Clone)]
+struct Liveness {
+    state: Arc<Mutex<LivenessState>>,
+}
+
+#[derive(Debug)]
+struct LivenessState {
+    last_seen: Instant,
+    handling: bool,
+}
+
+impl Liveness {
+    fn new() -> Liveness {
+        Liveness {
+            state: Arc::new(Mutex::new(LivenessState {
+                last_seen: Instant::now(),
+                handling: false,
+            })),
+        }
+    }
+
+    /// Called when a message was received, any message including pongs shows that the peer is alive
+    fn begin_message(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.last_seen = Instant::now();
+        state.handling = true;
+    }
+
+    /// Called once the message was handled, the peer has a full window to send the next one
+    fn end_message(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.last_seen = Instant::now();
+        state.handling = false;
+    }
+
+    /// Whether the peer sent nothing within the window and no message of it is being handled
+    fn is_idle(&self, window: Duration) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.handling && state.last_seen.elapsed() > window
+    }
+}
+
+/// Serves a websocket client over any stream, either a plain TCP stream or a TLS stream
+pub async fn accept_connection<S>(
+    stream: S,
+    addr: SocketAddr,
     available_tasks: Arc<Vec<GameTask>>,
-    redis_pool: Pool<RedisConnectionManager>,
+    settings: Arc<Settings>,
+    store: Store,
     sockets: Sockets,
     shard_id: String,
-) {
-    // Get ip address of peer
-    let addr = stream
-        .peer_addr()
-        .expect("connected streams should have a peer address");
-
-    let mut protocol = tokio_tungstenite::tungstenite::http::HeaderValue::from_static("");
-
-    let copy_headers_callback =
-        |request: &tokio_tungstenite::tungstenite::handshake::server::Request,
-         mut response: tokio_tungstenite::tungstenite::handshake::server::Response|
-         -> Result<
-            tokio_tungstenite::tungstenite::handshake::server::Response,
-            tokio_tungstenite::tungstenite::handshake::server::ErrorResponse,
-        > {
-            //access the protocol in the request, then set it in the response
-            protocol = request
-                .headers()
-                .get(tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL)
-                .expect("the client should specify a protocol")
-                .to_owned(); //save the protocol to use outside the closure
-            let response_protocol = request
-                .headers()
-                .get(tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL)
-                .expect("the client should specify a protocol")
-                .to_owned();
-            response.headers_mut().insert(
-                tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL,
-                response_protocol,
-            );
-            Ok(response)
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut protocol = Protocol::DEFAULT;
+    let mut user = None;
+    let mut rejection = RejectionReason::Handshake;
+
+    // The error type is dictated by the tungstenite handshake callback
+    #[allow(clippy::result_large_err)]
+    let negotiate_protocol_callback =
+        |request: &Request, mut response: Response| -> Result<Response, ErrorResponse> {
+            // Reject clients from unknown origins or with invalid tokens before anything else
+            match settings.authenticator.authenticate(request) {
+                Ok(authenticated) => user = authenticated, //save the user to use outside the closure
+                Err(auth_rejection) => {
+                    rejection = auth_rejection.into(); //save the reason to count the rejection
+                    let mut response = ErrorResponse::new(Some(auth_rejection.to_string()));
+                    *response.status_mut() = auth_rejection.status();
+                    return Err(response);
+                }
+            }
+
+            // Clients that do not request a protocol are served the default one
+            let requested = match request.headers().get(SEC_WEBSOCKET_PROTOCOL) {
+                Some(requested) => requested,
+                None => return Ok(response),
+            };
+
+            match Protocol::negotiate(requested) {
+                Some(negotiated) => {
+                    protocol = negotiated; //save the protocol to use outside the closure
+                    response.headers_mut().insert(
+                        SEC_WEBSOCKET_PROTOCOL,
+                        HeaderValue::from_static(negotiated.name()),
+                    );
+                    Ok(response)
+                }
+                None => {
+                    rejection = RejectionReason::Protocol;
+                    let mut response = ErrorResponse::new(Some(format!(
+                        "Unsupported protocol, supported protocols are: {}",
+                        Protocol::supported_names()
+                    )));
+                    *response.status_mut() = StatusCode::BAD_REQUEST;
+                    Err(response)
+                }
+            }
         };
 
+    // Messages larger than the limit are rejected by tungstenite and close the connection
+    let config = WebSocketConfig {
+        max_message_size: Some(settings.max_message_size),
+        max_frame_size: Some(settings.max_message_size),
+        ..WebSocketConfig::default()
+    };
+
     // Accept async websocket connection
-    let ws_stream = tokio_tungstenite::accept_hdr_async(stream, copy_headers_callback)
-        .await
-        .expect("Error during the websocket handshake occurred");
+    let ws_stream = match tokio_tungstenite::accept_hdr_async_with_config(
+        stream,
+        negotiate_protocol_callback,
+        Some(config),
+    )
+    .await
+    {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            let count = HANDSHAKE_REJECTIONS.record(rejection);
+            warn!(
+                "Rejected websocket handshake with {} ({}, {} so far): {}",
+                addr, rejection, count, e
+            );
+            return;
+        }
+    };
 
-    info!("New WebSocket connection: {}", addr);
+    info!("New WebSocket connection: {} using {}", addr, protocol);
 
     // Split websocket stream into two parts
     let (mut write, read) = ws_stream.split();
 
     // Create channel for communication from reader to writer
-    let (sender, mut receiver) = tokio::sync::mpsc::channel(200);
+    let (sender, mut receiver) = OutboundQueue::new();
 
-    // Register socket client
-    let client = SocketClient::new(addr, sender.clone());
-    sockets.insert(*client.id(), client.clone());
+    let client = SocketClient::new(
+        addr,
+        sender.clone(),
+        protocol,
+        user,
+        RateLimiter::new(&settings.rate_limits),
+    );
 
     superluminal_perf::begin_event_with_data(
         "websocket client",
@@ -75,14 +187,39 @@ pub async fn accept_connection(
         0x562db5,
     );
 
+    // Trigger on open event for socket client and register it in the global datastore of sockets,
+    // a client that fails either is disconnected before any of its messages are read
+    let opened = match client.on_open().await {
+        Ok(_) => client
+            .register(&store, shard_id.clone())
+            .await
+            .map_err(|e| (RejectionReason::Register, e.to_string())),
+        Err(e) => Err((RejectionReason::Hello, e.to_string())),
+    };
+    if let Err((rejection, e)) = opened {
+        let count = HANDSHAKE_REJECTIONS.record(rejection);
+        warn!(
+            "Rejected websocket connection with {} ({}, {} so far): {}",
+            addr, rejection, count, e
+        );
+        superluminal_perf::end_event();
+        return;
+    }
+    sockets.insert(*client.id(), client.clone());
+
+    // Shared by the reader, which sees the messages, and the writer, which sends the heartbeats
+    let liveness = Liveness::new();
+
     // Prepare reader task
     // This task reads all incoming messages from the **CLIENT** coming through the TcpListener
     let local_shard_id_message = shard_id.clone();
     let local_client_id = *client.id();
-    let local_redis_pool = redis_pool.clone();
+    let local_store = store.clone();
     let local_available_tasks = available_tasks.clone();
+    let local_settings = settings.clone();
     let local_sockets = sockets.clone();
-    let read_channel = tokio::spawn(async move {
+    let local_liveness = liveness.clone();
+    let mut read_channel = tokio::spawn(async move {
         let mut read_channel = read
             .try_filter(|message| future::ready(!message.is_close()))
             .enumerate();
@@ -92,19 +229,24 @@ pub async fn accept_connection(
                 Some(s) => match s.1 {
                     Ok(message) => {
                         // Trigger on_message(...) event
-                        match SocketClient::on_message(
+                        local_liveness.begin_message();
+                        let handled = SocketClient::on_message(
                             local_client_id,
-                            local_redis_pool.clone(),
+                            local_store.clone(),
                             local_available_tasks.clone(),
+                            local_settings.clone(),
                             message,
                             local_shard_id_message.clone(),
                             local_sockets.clone(),
                         )
-                        .await
-                        {
+                        .await;
+                        local_liveness.end_message();
+
+                        match handled {
                             Ok(should_close) => {
                                 if should_close {
                                     trace!("Reached an should_close point, disconnecting socket.");
+                                    superluminal_perf::end_event();
                                     return tokio_tungstenite::tungstenite::Error::ConnectionClosed;
                                 }
                             }
@@ -118,6 +260,7 @@ pub async fn accept_connection(
                     }
                 },
                 None => {
+                    superluminal_perf::end_event();
                     return tokio_tungstenite::tungstenite::Error::ConnectionClosed;
                 }
             }
@@ -126,82 +269,235 @@ pub async fn accept_connection(
     });
 
     // Handle messages sent from the SocketClient struct that was fetched from the connections hashmap
-    let write_channel = tokio::spawn(async move {
+    // and ping the client every heartbeat, closing the socket if the client stopped responding
+    let mut write_channel = tokio::spawn(async move {
+        let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
         loop {
             superluminal_perf::begin_event("websocket client write");
-            match receiver.recv().await {
-                Some(message) => match write.send(message).await {
-                    Ok(_) => {
-                        trace!("Sent message to websocket");
-                    }
-                    Err(e) => {
-                        error!(
-                            "Socket message could not be written to websocket write channel: {}",
-                            e
-                        );
+            tokio::select! {
+                message = receiver.recv() => match message {
+                    Some(message) => match write.send(message).await {
+                        Ok(_) => {
+                            trace!("Sent message to websocket");
+                        }
+                        Err(e) => {
+                            error!(
+                                "Socket message could not be written to websocket write channel: {}",
+                                e
+                            );
+                        }
+                    },
+                    None => {
+                        info!("No more senders are active, terminating socket");
+                        superluminal_perf::end_event();
+                        break;
                     }
                 },
-                None => {
-                    info!("No more senders are active, terminating socket");
-                    break;
+                _ = heartbeat.tick() => {
+                    if liveness.is_idle(HEARTBEAT_INTERVAL * MAX_MISSED_HEARTBEATS) {
+                        info!("Socket missed {} heartbeats, closing it", MAX_MISSED_HEARTBEATS);
+                        let _ = write.send(Message::Close(None)).await;
+                        superluminal_perf::end_event();
+                        break;
+                    }
+
+                    if let Err(e) = write.send(Message::Ping(Vec::new())).await {
+                        error!("Heartbeat could not be written to websocket write channel: {}", e);
+                    }
                 }
             }
             superluminal_perf::end_event();
         }
     });
 
-    // Trigger on open event for socket client
-    sockets
-        .get_mut(client.id())
-        .expect("socket connection does not exist")
-        .on_open()
+    // Serve the client until the reader or writer stops, a client that stopped reading is
+    // disconnected as soon as its outbound queue is saturated
+    tokio::select! {
+        _ = &mut read_channel => {}
+        _ = &mut write_channel => {}
+        _ = sender.saturated() => {
+            warn!("Outbound queue of {} is saturated, disconnecting", addr);
+        }
+    }
+
+    // Whichever task finished first, the other one must not outlive the connection
+    read_channel.abort();
+    write_channel.abort();
+
+    // Trigger on close event, which removes the socket locally and from the global datastore
+    SocketClient::on_close(*client.id(), &sockets, &store).await;
+    info!("Socket disconnected: {}", addr);
+
+    superluminal_perf::end_event();
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt, DuplexStream},
+        task::JoinHandle,
+    };
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use uuid::Uuid;
+
+    use crate::service::{state_store::memory::MemoryStateStore, websocket::auth::Authenticator};
+
+    use super::*;
+
+    const UPGRADE: &str = "GET / HTTP/1.1\r\n\
+        Host: localhost\r\n\
+        Connection: Upgrade\r\n\
+        Upgrade: websocket\r\n\
+        Sec-WebSocket-Version: 13\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n";
+
+    /// Serves a connection over an in-memory stream, returns the peer end of the stream
+    fn connect(settings: Settings, sockets: &Sockets) -> (DuplexStream, JoinHandle<()>) {
+        let (peer, stream) = tokio::io::duplex(64 << 10);
+        let connection = tokio::spawn(accept_connection(
+            stream,
+            "127.0.0.1:1234".parse().unwrap(),
+            Arc::new(vec![]),
+            Arc::new(settings),
+            Arc::new(MemoryStateStore::default()),
+            sockets.clone(),
+            "shard".to_owned(),
+        ));
+
+        (peer, connection)
+    }
+
+    /// Sends a raw handshake and returns everything the server answered until it hung up
+    async fn handshake(settings: Settings, request: &[u8]) -> (String, Sockets) {
+        let sockets: Sockets = Arc::new(DashMap::new());
+        let (mut peer, connection) = connect(settings, &sockets);
+        peer.write_all(request).await.unwrap();
+        peer.shutdown().await.unwrap();
+
+        let mut response = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            let _ = peer.read_to_end(&mut response).await;
+            connection.await.unwrap();
+        })
+        .await
+        .expect("malformed handshake was not rejected");
+
+        (String::from_utf8_lossy(&response).into_owned(), sockets)
+    }
+
+    /// Runs a malformed handshake and checks that it was rejected for the expected reason
+    async fn assert_rejected(
+        settings: Settings,
+        request: &[u8],
+        reason: RejectionReason,
+    ) -> String {
+        let rejected = HANDSHAKE_REJECTIONS.count(reason);
+        let (response, sockets) = handshake(settings, request).await;
+
+        assert!(sockets.is_empty());
+        assert!(HANDSHAKE_REJECTIONS.count(reason) > rejected);
+        response
+    }
+
+    #[tokio::test]
+    async fn rejects_garbage() {
+        let response = assert_rejected(
+            Settings::default(),
+            b"\x16\x03\x01\x02\x00\x01\x00\x01\xfc\x03\x03\r\n\r\n",
+            RejectionReason::Handshake,
+        )
         .await;
+        assert!(!response.contains("101"));
+    }
 
-    // Registers the socket in the global datastore of sockets
-    let res = sockets
-        .get(client.id())
-        .expect("socket connection does not exist")
-        .register(&redis_pool, shard_id);
-    match res {
-        Ok(_) => {}
-        Err(e) => {
-            error!(
-                "An error occured while registering user on the global socket datastore: {}",
-                e
-            );
-            sockets.remove(client.id());
-        }
+    #[tokio::test]
+    async fn rejects_closed_and_truncated_handshakes() {
+        assert_rejected(Settings::default(), b"", RejectionReason::Handshake).await;
+        assert_rejected(
+            Settings::default(),
+            UPGRADE.as_bytes(),
+            RejectionReason::Handshake,
+        )
+        .await;
     }
 
-    // Start the reader and writer tasks
-    futures::pin_mut!(read_channel, write_channel);
-    let _ = futures::future::select(read_channel, write_channel).await;
+    #[tokio::test]
+    async fn rejects_requests_that_are_not_upgrades() {
+        assert_rejected(
+            Settings::default(),
+            b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            RejectionReason::Handshake,
+        )
+        .await;
+        assert_rejected(
+            Settings::default(),
+            b"POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n",
+            RejectionReason::Handshake,
+        )
+        .await;
+    }
 
-    // Trigger on close event
-    sockets
-        .get_mut(client.id())
-        .expect("socket connection does not exist")
-        .on_close();
+    #[tokio::test]
+    async fn rejects_unsupported_protocols() {
+        let request = format!("{}Sec-WebSocket-Protocol: xml\r\n\r\n", UPGRADE);
+        let response = assert_rejected(
+            Settings::default(),
+            request.as_bytes(),
+            RejectionReason::Protocol,
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 400"));
+    }
 
-    // Unregisters the socket in the global datastore of sockets
-    let res = sockets
-        .get(client.id())
-        .expect("socket connection does not exist")
-        .unregister(&redis_pool);
-    match res {
-        Ok(_) => {}
-        Err(e) => {
-            // Todo: Handle error, would be an excellent idea if the user was unregistered correctly.
-            error!(
-                "An error occured while unregistering user on the global socket datastore: {}",
-                e
-            );
-        }
+    #[tokio::test]
+    async fn rejects_origins_that_are_not_allowed() {
+        let settings = Settings {
+            authenticator: Authenticator::new(vec!["https://example.com".to_owned()], None, false)
+                .unwrap(),
+            ..Settings::default()
+        };
+        let request = format!("{}Origin: https://example.org\r\n\r\n", UPGRADE);
+        let response = assert_rejected(settings, request.as_bytes(), RejectionReason::Origin).await;
+        assert!(response.starts_with("HTTP/1.1 403"));
     }
 
-    // Remove the socket locally
-    sockets.remove(client.id());
-    info!("Socket disconnected: {}", addr);
+    #[tokio::test]
+    async fn registers_clients_after_the_handshake() {
+        let sockets: Sockets = Arc::new(DashMap::new());
+        let (peer, connection) = connect(Settings::default(), &sockets);
 
-    superluminal_perf::end_event();
+        let request = "ws://localhost/".into_client_request().unwrap();
+        let (mut ws_stream, _) = tokio_tungstenite::client_async(request, peer)
+            .await
+            .unwrap();
+        let hello = ws_stream.next().await.unwrap().unwrap();
+        let hello: serde_json::Value = serde_json::from_str(hello.to_text().unwrap()).unwrap();
+        let client_id: Uuid = serde_json::from_value(hello["d"]["id"].clone()).unwrap();
+        assert!(sockets.contains_key(&client_id));
+
+        ws_stream.close(None).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), connection)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(sockets.is_empty());
+    }
+
+    #[tokio::test]
+    async fn clients_are_not_idle_while_a_message_outlasts_the_heartbeat_window() {
+        let window = Duration::from_millis(20);
+        let liveness = Liveness::new();
+
+        liveness.begin_message();
+        tokio::time::sleep(window * 2).await;
+        assert!(!liveness.is_idle(window));
+
+        // The window restarts once the message was handled
+        liveness.end_message();
+        assert!(!liveness.is_idle(window));
+
+        tokio::time::sleep(window * 2).await;
+        assert!(liveness.is_idle(window));
+    }
 }
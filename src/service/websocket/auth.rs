@@ -0,0 +1,266 @@
+use std::fmt;
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use tokio_tungstenite::tungstenite::{
+    handshake::server::Request,
+    http::{
+        header::{ORIGIN, SEC_WEBSOCKET_PROTOCOL},
+        StatusCode,
+    },
+};
+
+/// Prefix of the subprotocol entry that carries a bearer token, e.g. "bearer.<token>",
+/// for clients that can not add a query string to the websocket url. The client still has to
+/// offer a supported protocol next to it, as browsers require the server to echo one of them
+const TOKEN_SUBPROTOCOL_PREFIX: &str = "bearer.";
+
+/// Account the client authenticated as during the handshake
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub(crate) user_id: String,
+    pub(crate) display_name: Option<String>,
+}
+
+/// Claims of the HS256 signed bearer tokens, the expiry is always validated
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    name: Option<String>,
+}
+
+/// Reason a handshake was rejected, sent to the client as an HTTP error response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthRejection {
+    OriginNotAllowed,
+    MissingToken,
+    InvalidToken,
+}
+
+impl AuthRejection {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AuthRejection::OriginNotAllowed => StatusCode::FORBIDDEN,
+            AuthRejection::MissingToken | AuthRejection::InvalidToken => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+impl fmt::Display for AuthRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthRejection::OriginNotAllowed => write!(f, "Origin is not allowed"),
+            AuthRejection::MissingToken => write!(f, "A bearer token is required"),
+            AuthRejection::InvalidToken => write!(f, "Bearer token is invalid or expired"),
+        }
+    }
+}
+
+/// Checks the origin and bearer token of websocket handshakes
+pub struct Authenticator {
+    /// Allowed values of the Origin header, every origin is allowed if empty
+    allowed_origins: Vec<String>,
+
+    /// Key the bearer tokens are signed with, tokens are ignored if None
+    token_secret: Option<Vec<u8>>,
+
+    /// If clients without a token are rejected
+    require_token: bool,
+}
+
+impl Authenticator {
+    /// Fails if tokens are required without a secret to verify them with,
+    /// which would let every client through
+    pub fn new(
+        allowed_origins: Vec<String>,
+        token_secret: Option<String>,
+        require_token: bool,
+    ) -> Result<Authenticator, String> {
+        if require_token && token_secret.is_none() {
+            return Err("require_auth is set without an auth_token_secret".to_owned());
+        }
+
+        Ok(Authenticator {
+            allowed_origins,
+            token_secret: token_secret.map(String::into_bytes),
+            require_token,
+        })
+    }
+
+    /// Checks a handshake request, returns the user if the client sent a valid token
+    pub fn authenticate(
+        &self,
+        request: &Request,
+    ) -> Result<Option<AuthenticatedUser>, AuthRejection> {
+        if !self.allowed_origins.is_empty() {
+            let origin = request
+                .headers()
+                .get(ORIGIN)
+                .and_then(|origin| origin.to_str().ok())
+                .ok_or(AuthRejection::OriginNotAllowed)?;
+            if !self.allowed_origins.iter().any(|allowed| allowed == origin) {
+                return Err(AuthRejection::OriginNotAllowed);
+            }
+        }
+
+        let secret = match &self.token_secret {
+            Some(secret) => secret,
+            None => return Ok(None),
+        };
+
+        let token = match Self::bearer_token(request) {
+            Some(token) => token,
+            None if self.require_token => return Err(AuthRejection::MissingToken),
+            None => return Ok(None),
+        };
+
+        let claims = jsonwebtoken::decode::<Claims>(
+            &token,
+            &DecodingKey::from_secret(secret),
+            &Validation::new(Algorithm::HS256),
+        )
+        .map_err(|_| AuthRejection::InvalidToken)?
+        .claims;
+
+        Ok(Some(AuthenticatedUser {
+            user_id: claims.sub,
+            display_name: claims.name,
+        }))
+    }
+
+    /// Finds the token in the percent-encoded "token" query parameter or in the subprotocol list
+    fn bearer_token(request: &Request) -> Option<String> {
+        let from_query = request.uri().query().and_then(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "token")
+                .map(|(_, token)| token.into_owned())
+        });
+
+        from_query.or_else(|| {
+            request
+                .headers()
+                .get(SEC_WEBSOCKET_PROTOCOL)?
+                .to_str()
+                .ok()?
+                .split(',')
+                .find_map(|protocol| protocol.trim().strip_prefix(TOKEN_SUBPROTOCOL_PREFIX))
+                .map(|token| token.to_owned())
+        })
+    }
+}
+
+impl fmt::Debug for Authenticator {
+    // The token secret is never printed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Authenticator")
+            .field("allowed_origins", &self.allowed_origins)
+            .field("verifies_tokens", &self.token_secret.is_some())
+            .field("require_token", &self.require_token)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    use super::*;
+
+    const SECRET: &str = "secret";
+
+    fn request(uri: &str) -> Request {
+        Request::builder().uri(uri).body(()).unwrap()
+    }
+
+    fn token(secret: &str) -> String {
+        jsonwebtoken::encode(
+            &Header::default(),
+            &json!({ "sub": "user", "name": "User", "exp": u32::MAX }),
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn required_tokens_need_a_secret() {
+        assert!(Authenticator::new(vec![], None, true).is_err());
+        assert!(Authenticator::new(vec![], Some(SECRET.to_owned()), true).is_ok());
+        assert!(Authenticator::new(vec![], None, false).is_ok());
+    }
+
+    #[test]
+    fn query_token_is_percent_decoded() {
+        assert_eq!(
+            Authenticator::bearer_token(&request("/?game=1&token=a%2Eb%2Bc+d")),
+            Some("a.b+c d".to_owned())
+        );
+        assert_eq!(Authenticator::bearer_token(&request("/?tokens=a")), None);
+    }
+
+    #[test]
+    fn authenticates_query_and_subprotocol_tokens() {
+        let authenticator = Authenticator::new(vec![], Some(SECRET.to_owned()), true).unwrap();
+
+        let encoded = token(SECRET).replace('.', "%2E");
+        let user = authenticator
+            .authenticate(&request(&format!("/?token={}", encoded)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.user_id, "user");
+        assert_eq!(user.display_name.as_deref(), Some("User"));
+
+        let request = Request::builder()
+            .uri("/")
+            .header(
+                SEC_WEBSOCKET_PROTOCOL,
+                format!("json, {}{}", TOKEN_SUBPROTOCOL_PREFIX, token(SECRET)),
+            )
+            .body(())
+            .unwrap();
+        assert!(authenticator.authenticate(&request).unwrap().is_some());
+    }
+
+    #[test]
+    fn rejects_missing_and_invalid_tokens() {
+        let authenticator = Authenticator::new(vec![], Some(SECRET.to_owned()), true).unwrap();
+
+        assert_eq!(
+            authenticator.authenticate(&request("/")).unwrap_err(),
+            AuthRejection::MissingToken
+        );
+        assert_eq!(
+            authenticator
+                .authenticate(&request(&format!("/?token={}", token("other"))))
+                .unwrap_err(),
+            AuthRejection::InvalidToken
+        );
+    }
+
+    #[test]
+    fn rejects_origins_that_are_not_allowed() {
+        let authenticator =
+            Authenticator::new(vec!["https://example.com".to_owned()], None, false).unwrap();
+        let from = |origin: &str| {
+            Request::builder()
+                .uri("/")
+                .header(ORIGIN, origin)
+                .body(())
+                .unwrap()
+        };
+
+        assert!(authenticator
+            .authenticate(&from("https://example.com"))
+            .is_ok());
+        assert_eq!(
+            authenticator
+                .authenticate(&from("https://example.org"))
+                .unwrap_err(),
+            AuthRejection::OriginNotAllowed
+        );
+        assert_eq!(
+            authenticator.authenticate(&request("/")).unwrap_err(),
+            AuthRejection::OriginNotAllowed
+        );
+    }
+}
@@ -1,13 +1,12 @@
 use std::{net::SocketAddr, sync::Arc};
 
-use r2d2::Pool;
-use redis::Commands;
 use serde_json::Value;
 use tokio_tungstenite::tungstenite::Message;
 use uuid::Uuid;
 
 use crate::service::{
-    redis_pool::RedisConnectionManager,
+    settings::Settings,
+    state_store::{StateError, Store},
     websocket::client::game::models::{
         response::timeout::TimeoutResponse, Response, ResponseOpCode,
     },
@@ -15,18 +14,26 @@ use crate::service::{
 };
 
 use self::{
-    error::ClientError,
+    error::{ClientError, ClientErrorModel},
     game::{task::GameTask, Game},
-    models::{forced_disconnection::ForcedDisconnection, hello::Hello, DefaultModel},
+    models::{hello::Hello, DefaultModel},
+    rate_limit::RateLimiter,
 };
 use message_handler::ClientMessageHandler;
 
-use super::SocketSender;
+use super::{
+    auth::AuthenticatedUser,
+    outbound::Priority,
+    protocol::{CodecError, Protocol},
+    SocketSender,
+};
 
 pub mod error;
 pub mod game;
 pub mod message_handler;
 pub mod models;
+pub mod nickname;
+pub mod rate_limit;
 
 #[derive(Debug, Clone)]
 pub struct SocketClient {
@@ -34,74 +41,111 @@ pub struct SocketClient {
     pub(crate) addr: SocketAddr,
     pub(crate) send_channel: SocketSender,
 
+    /// Protocol version negotiated during the handshake
+    pub(crate) protocol: Protocol,
+
     /// Some(...) if user is in a game
     pub(crate) game: Option<Game>,
     pub(crate) nickname: Option<String>,
 
-    /// True if the shutdown was done through event on_close
-    performed_safe_shutdown: bool,
+    /// Preferred locale sent with Identify, used to translate task statements
+    pub(crate) locale: Option<String>,
+
+    /// Account authenticated with a bearer token during the handshake, None for guests
+    pub(crate) user: Option<AuthenticatedUser>,
+
+    /// Limits how often the client can send each request op code
+    pub(crate) rate_limiter: RateLimiter,
 
+    /// Ids of the games the client created, removed from the state store when the shard drains
+    /// unless a client joined and hosts them by then
+    pub(crate) created_games: Vec<String>,
 }
 
 impl SocketClient {
-    pub fn new(addr: SocketAddr, send_channel: SocketSender) -> SocketClient {
+    pub fn new(
+        addr: SocketAddr,
+        send_channel: SocketSender,
+        protocol: Protocol,
+        user: Option<AuthenticatedUser>,
+        rate_limiter: RateLimiter,
+    ) -> SocketClient {
         SocketClient {
             id: uuid::Uuid::new_v4(),
             addr,
             send_channel,
+            protocol,
             game: None,
             nickname: None,
-            performed_safe_shutdown: false,
+            locale: None,
+            user,
+            rate_limiter,
+            created_games: Vec::new(),
         }
     }
 
     /// Triggered once the client has been registered and is connected
     ///
     /// Sends a hello with the socket id
-    pub async fn on_open(&mut self) {
-        trace!("Client connected with address {}", self.addr);
+    pub async fn on_open(&self) -> Result<(), ClientError<'static>> {
+        match &self.user {
+            Some(user) => trace!(
+                "Client connected with address {} using {} as user {} ({})",
+                self.addr,
+                self.protocol,
+                user.user_id,
+                user.display_name.as_deref().unwrap_or("no display name")
+            ),
+            None => trace!(
+                "Client connected with address {} using {}",
+                self.addr,
+                self.protocol
+            ),
+        }
 
         let model = DefaultModel::new(Hello { id: self.id });
-        self.send_model(model)
-            .await
-            .expect("could not send hello message");
+        self.send_model(model).await
     }
 
     /// Triggered when connection is closing
-    pub fn on_close(&mut self) {
-        self.performed_safe_shutdown = true;
-        if let Some(game) = self.game.take() {
-            drop(game);
+    ///
+    /// Removes the client, leaves its game and unregisters it from the global datastore of sockets
+    pub async fn on_close(client_id: Uuid, sockets: &Sockets, store: &Store) {
+        // The socket is removed first, leaving a game locks the socket of the host
+        let mut client = match sockets.remove(&client_id) {
+            Some((_, client)) => client,
+            None => return,
+        };
+
+        if let Some(game) = client.game.take() {
+            game.close().await;
+        }
+
+        if let Err(e) = client.unregister(store).await {
+            // Todo: Handle error, would be an excellent idea if the user was unregistered correctly.
+            error!(
+                "An error occured while unregistering user on the global socket datastore: {}",
+                e
+            );
         }
     }
 
     /// Registers the socket client in the global connection datastore
-    pub fn register(
-        &self,
-        redis_pool: &Pool<RedisConnectionManager>,
-        shard_id: String,
-    ) -> Result<(), String> {
-        let mut conn = redis_pool.get().map_err(|e| e.to_string())?;
-        let _: () = conn
-            .set(format!("SOCKET:USER:{}", self.id), shard_id)
-            .map_err(|e| e.to_string())?;
-        Ok(())
+    pub async fn register(&self, store: &Store, shard_id: String) -> Result<(), StateError> {
+        store.register_socket(&self.id, &shard_id).await
     }
 
     /// Unregisters the socket client from the global connection datastore
-    pub fn unregister(&self, redis_pool: &Pool<RedisConnectionManager>) -> Result<(), String> {
-        let mut conn = redis_pool.get().map_err(|e| e.to_string())?;
-        let _ = conn
-            .del(format!("SOCKET:USER:{}", self.id))
-            .map_err(|e| e.to_string())?;
-        Ok(())
+    pub async fn unregister(&self, store: &Store) -> Result<(), StateError> {
+        store.unregister_socket(&self.id).await
     }
 
     /// Called when a client receives a new message
     pub async fn on_message<'a>(
         client_id: Uuid,
-        redis_pool: Pool<RedisConnectionManager>,
+        store: Store,
         available_tasks: Arc<Vec<GameTask>>,
+        settings: Arc<Settings>,
         message: Message,
         shard_id: String,
         sockets: Sockets,
@@ -109,38 +153,47 @@ impl SocketClient {
         superluminal_perf::begin_event("on message");
         let mut should_close = false;
         match message {
-            Message::Text(text) => {
-                // Try to parse the message according to the Default JSON layout
-                let model: Result<DefaultModel<Value>, serde_json::Error> =
-                    serde_json::from_str(&text);
-                match model {
+            Message::Text(_) | Message::Binary(_) => {
+                // Try to parse the message according to the Default layout of the negotiated protocol
+                let protocol = sockets.get(&client_id).unwrap().protocol;
+                let model: Result<DefaultModel<Value>, CodecError> = protocol.decode(&message);
+                match model.map(DefaultModel::with_request_nonce) {
                     Ok(model) => {
                         match tokio::time::timeout(
                             std::time::Duration::from_secs(50),
                             ClientMessageHandler::handle_message(
                                 client_id,
                                 &sockets,
-                                redis_pool,
+                                store,
                                 available_tasks,
+                                settings,
                                 &model,
                                 &shard_id,
                             ),
                         )
                         .await
                         {
-                            Ok(res) => {
-                                if let Err(e) = res {
-                                    error!("Error while handling message {}", e);
+                            Ok(res) => match res {
+                                Err(e) if e.is_fatal() => {
+                                    error!("Fatal error while handling message {}", e);
                                     should_close = true;
                                 }
-                            }
+                                Err(e) => {
+                                    debug!("Recoverable error while handling message {}", e);
+                                }
+                                Ok(_) => {}
+                            },
                             Err(_) => {
+                                let nonce = model.nonce.clone();
                                 let client = sockets.get(&client_id).unwrap().clone();
                                 client
-                                    .send_model(DefaultModel::new(Response::new(
-                                        Some(TimeoutResponse::new(model)),
-                                        ResponseOpCode::Timeout,
-                                    )))
+                                    .send_model(
+                                        DefaultModel::new(Response::new(
+                                            Some(TimeoutResponse::new(model)),
+                                            ResponseOpCode::Timeout,
+                                        ))
+                                        .with_nonce(nonce.as_deref()),
+                                    )
                                     .await
                                     .map_err(|_| ClientError::SendError)?;
                                 trace!("Message handling timed out, disconnecting client.");
@@ -154,8 +207,11 @@ impl SocketClient {
                         sockets
                             .get(&client_id)
                             .unwrap()
-                            .send_error(ClientError::InvalidMessage(
-                                "Invalid model, closing connection.",
+                            .send_error(ClientErrorModel::new(
+                                &ClientError::InvalidMessage("Invalid model, closing connection."),
+                                None,
+                                None,
+                                true,
                             ))
                             .await
                             .map_err(|_| ClientError::SendError)?;
@@ -168,19 +224,14 @@ impl SocketClient {
                 .send(Message::Pong(bin))
                 .await
                 .map_err(|_| ClientError::SendError)?,
-            Message::Pong(bin) => {
-                sockets
-                    .get(&client_id)
-                    .unwrap()
-                    .send(Message::Ping(bin))
-                    .await
-                    .map_err(|_| ClientError::SendError)?;
+            Message::Pong(_) => {
+                // Answers to heartbeats, the read task already marked the client as seen
+                trace!("Received heartbeat pong");
             }
             Message::Close(reason) => {
                 info!("Received close message: {:?}", reason);
                 return Ok(true);
             }
-            _ => {}
         }
 
         superluminal_perf::end_event();
@@ -188,28 +239,37 @@ impl SocketClient {
     }
 
     /// Sends a error to the client
+    ///
+    /// The nonce of the request that caused the error is echoed back if there is one
     #[inline]
-    pub async fn send_error<'a>(&self, err: ClientError<'_>) -> Result<(), ClientError<'a>> {
-        error!("Sending error {} to client", err);
-        self.send_model(DefaultModel::new(err)).await
+    pub async fn send_error<'a>(&self, error: ClientErrorModel) -> Result<(), ClientError<'a>> {
+        error!("Sending error {} ({}) to client", error.kind, error.code);
+        let nonce = error.nonce.clone();
+        self.send_model(DefaultModel::new(error).with_nonce(nonce.as_deref()))
+            .await
     }
 
-    /// Sends a model (JSON serializable object) to the client
+    /// Sends a model (serializable object) to the client, encoded with the negotiated protocol
     #[inline]
-    pub async fn send_model<'a, 'b, T>(&self, default: DefaultModel<T>) -> Result<(), ClientError<'b>>
+    pub async fn send_model<'a, 'b, T>(
+        &self,
+        default: DefaultModel<T>,
+    ) -> Result<(), ClientError<'b>>
     where
         T: serde::Serialize + serde::Deserialize<'a>,
     {
-        self.send(Message::Text(serde_json::to_string(&default).unwrap()))
-            .await
+        let message = self
+            .protocol
+            .encode(&default)
+            .map_err(|_| ClientError::SendError)?;
+        self.send(message).await
     }
 
     /// Sends a raw websocket message
     #[inline]
     pub async fn send<'a>(&self, message: Message) -> Result<(), ClientError<'a>> {
         self.send_channel
-            .send(message)
-            .await
+            .push(message, Priority::Normal)
             .map_err(|_| ClientError::SendError)
     }
 
@@ -219,14 +279,3 @@ impl SocketClient {
         &self.id
     }
 }
-
-impl Drop for SocketClient {
-    fn drop(&mut self) {
-        // If the client wasn't safely closed, send shutdown event to client
-        if !self.performed_safe_shutdown {
-            let _ = futures::executor::block_on(
-                self.send_model(DefaultModel::new(ForcedDisconnection {})),
-            );
-        }
-    }
-}
@@ -1,8 +1,12 @@
 use std::fmt;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::models::{OpCode, OpCodeFetcher};
+use super::{
+    game::models::request::RequestOpCode,
+    models::{OpCode, OpCodeFetcher},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ClientError<'a> {
@@ -18,24 +22,174 @@ pub enum ClientError<'a> {
     NoGameWasFound,
     GameNotStarted,
     GameAlreadyStarted,
+    ChatDisabled,
+    ChatMuted,
+    ChatRateLimited,
+    ChatMessageTooLong,
     ClientNotIdentified,
     InvalidGameID,
     InvalidOpCode,
     ParsingError,
     SendError,
+
+    /// Too many requests with the same op code, retry after the given amount of milliseconds
+    RateLimited(u64),
 }
 
-impl<'a> OpCodeFetcher for ClientError<'a> {
-    #[inline]
-    fn op_code() -> OpCode {
-        OpCode::Error
+impl<'a> ClientError<'a> {
+    /// Stable numeric code of the error, codes are never reused for another kind of error
+    ///
+    /// 1xxx are malformed messages, 2xxx are requests that are invalid in the current game state,
+    /// 3xxx are chat errors, 4xxx are rate limits and 5xxx are server errors
+    pub fn code(&self) -> u16 {
+        match self {
+            ClientError::InvalidMessage(_) => 1000,
+            ClientError::NoDataWithOpCode(_) => 1001,
+            ClientError::InvalidOpCode => 1002,
+            ClientError::ParsingError => 1003,
+            ClientError::ClientNotIdentified => 1004,
+            ClientError::ClientDoesNotExist(_) => 2000,
+            ClientError::AlreadyInGame(_) => 2001,
+            ClientError::NotInGame(_) => 2002,
+            ClientError::NotGameHost(_) => 2003,
+            ClientError::NoGameWasFound => 2004,
+            ClientError::InvalidGameID => 2005,
+            ClientError::GameNotStarted => 2006,
+            ClientError::GameAlreadyStarted => 2007,
+            ClientError::OutOfRangeTask => 2008,
+            ClientError::CompilationError(_) => 2009,
+            ClientError::ChatDisabled => 3000,
+            ClientError::ChatMuted => 3001,
+            ClientError::ChatRateLimited => 3002,
+            ClientError::ChatMessageTooLong => 3003,
+            ClientError::RateLimited(_) => 4000,
+            ClientError::InternalServerError(_) => 5000,
+            ClientError::SendError => 5001,
+        }
+    }
+
+    /// Machine readable name of the error
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientError::InvalidMessage(_) => "InvalidMessage",
+            ClientError::NoDataWithOpCode(_) => "NoDataWithOpCode",
+            ClientError::InvalidOpCode => "InvalidOpCode",
+            ClientError::ParsingError => "ParsingError",
+            ClientError::ClientNotIdentified => "ClientNotIdentified",
+            ClientError::ClientDoesNotExist(_) => "ClientDoesNotExist",
+            ClientError::AlreadyInGame(_) => "AlreadyInGame",
+            ClientError::NotInGame(_) => "NotInGame",
+            ClientError::NotGameHost(_) => "NotGameHost",
+            ClientError::NoGameWasFound => "NoGameWasFound",
+            ClientError::InvalidGameID => "InvalidGameID",
+            ClientError::GameNotStarted => "GameNotStarted",
+            ClientError::GameAlreadyStarted => "GameAlreadyStarted",
+            ClientError::OutOfRangeTask => "OutOfRangeTask",
+            ClientError::CompilationError(_) => "CompilationError",
+            ClientError::ChatDisabled => "ChatDisabled",
+            ClientError::ChatMuted => "ChatMuted",
+            ClientError::ChatRateLimited => "ChatRateLimited",
+            ClientError::ChatMessageTooLong => "ChatMessageTooLong",
+            ClientError::RateLimited(_) => "RateLimited",
+            ClientError::InternalServerError(_) => "InternalServerError",
+            ClientError::SendError => "SendError",
+        }
+    }
+
+    /// Fatal errors are protocol violations and close the connection,
+    /// all other errors are answered and the client can keep using the socket
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            ClientError::InvalidMessage(_)
+                | ClientError::NoDataWithOpCode(_)
+                | ClientError::InvalidOpCode
+                | ClientError::SendError
+        )
+    }
+
+    /// Human readable description of the error
+    pub fn message(&self) -> &'a str {
+        match self {
+            ClientError::InvalidMessage(message)
+            | ClientError::ClientDoesNotExist(message)
+            | ClientError::AlreadyInGame(message)
+            | ClientError::NotInGame(message)
+            | ClientError::InternalServerError(message)
+            | ClientError::NotGameHost(message)
+            | ClientError::NoDataWithOpCode(message)
+            | ClientError::CompilationError(message) => message,
+            ClientError::OutOfRangeTask => "Task index is out of range",
+            ClientError::NoGameWasFound => "No game was found",
+            ClientError::GameNotStarted => "Game has not started",
+            ClientError::GameAlreadyStarted => "Game has already started",
+            ClientError::ChatDisabled => "Chat is disabled in this game",
+            ClientError::ChatMuted => "Client is muted in this game",
+            ClientError::ChatRateLimited => "Too many chat messages, slow down",
+            ClientError::ChatMessageTooLong => "Chat message is too long",
+            ClientError::RateLimited(_) => "Too many requests, retry later",
+            ClientError::ClientNotIdentified => "Client has not identified itself",
+            ClientError::InvalidGameID => "Invalid game id",
+            ClientError::InvalidOpCode => "Invalid op code",
+            ClientError::ParsingError => "Message could not be parsed",
+            ClientError::SendError => "Message could not be sent",
+        }
     }
 }
 
 impl<'a> fmt::Display for ClientError<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Error \"{:?}\"", self)
+        write!(f, "{} ({}): {}", self.kind(), self.code(), self.message())
     }
 }
 
 impl<'a> std::error::Error for ClientError<'a> {}
+
+/// Error as it is sent to clients
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ClientErrorModel {
+    /// Stable numeric code, see `ClientError::code`
+    pub(crate) code: u16,
+    pub(crate) kind: String,
+    pub(crate) message: String,
+
+    /// Op code of the request that caused the error, None if the message could not be parsed as a request
+    pub(crate) op: Option<RequestOpCode>,
+    pub(crate) nonce: Option<String>,
+
+    /// If the connection is closed after the error
+    pub(crate) fatal: bool,
+
+    /// Milliseconds to wait before retrying, only set if the request was rate limited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) retry_after_ms: Option<u64>,
+}
+
+impl ClientErrorModel {
+    pub fn new(
+        error: &ClientError<'_>,
+        op: Option<RequestOpCode>,
+        nonce: Option<String>,
+        fatal: bool,
+    ) -> Self {
+        ClientErrorModel {
+            code: error.code(),
+            kind: error.kind().to_owned(),
+            message: error.message().to_owned(),
+            op,
+            nonce,
+            fatal,
+            retry_after_ms: match error {
+                ClientError::RateLimited(retry_after_ms) => Some(*retry_after_ms),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl OpCodeFetcher for ClientErrorModel {
+    #[inline]
+    fn op_code() -> OpCode {
+        OpCode::Error
+    }
+}
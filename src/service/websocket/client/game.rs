@@ -1,13 +1,15 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use r2d2::Pool;
 use rand::prelude::SliceRandom;
-use redis::Commands;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::service::{
-    redis_pool::RedisConnectionManager,
+    state_store::Store,
     websocket::client::{
         game::models::{
             event::{connected_client::ConnectedClientGameEvent, shutdown::ShutdownGameEvent},
@@ -15,34 +17,67 @@ use crate::service::{
             GameEvent, Response, ResponseOpCode,
         },
         models::DefaultModel,
+        nickname,
     },
+    websocket::outbound::Priority,
     Sockets,
 };
 
 use self::{
+    language::Language,
     models::{
         event::{
-            disconnected_client::DisconnectedClientGameEvent, start::StartGameEvent,
-            task_finished::TaskFinishedGameEvent,
+            chat_message::ChatMessageGameEvent, disconnected_client::DisconnectedClientGameEvent,
+            start::StartGameEvent, task_finished::TaskFinishedGameEvent,
         },
-        response::compile::{progress::PublicTestProgress, CompilationResponse},
+        response::compile::{progress::PublicTestProgress, verdict::Verdict, CompilationResponse},
     },
     partial_client::PartialClient,
     sandbox::{sandbox_service_client::SandboxServiceClient, SandboxRequest, SandboxResponse},
-    task::GameTask,
+    task::{limits::TaskLimits, view::TaskStatementView, GameTask, SandboxInput},
+    verdict_cache::VerdictCache,
 };
 
 use super::error::ClientError;
 
+pub mod language;
 pub mod models;
 pub mod partial_client;
 pub mod redis_game;
 pub mod task;
+pub mod verdict_cache;
 
 pub mod sandbox {
     tonic::include_proto!("sandbox");
 }
 
+/// Opaque handle that identifies a player within a single game,
+/// socket ids are never shared with other clients
+pub type PlayerId = u32;
+
+/// Maximum length of a chat message, counted in characters
+const MAX_CHAT_MESSAGE_LENGTH: usize = 256;
+
+/// Maximum amount of chat messages a client can send within `CHAT_RATE_LIMIT_WINDOW`
+const CHAT_RATE_LIMIT_MESSAGES: usize = 5;
+const CHAT_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+
+/// How long a shared verdict is kept in the state store
+const VERDICT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum amount of verdicts a game keeps in memory
+const VERDICT_CACHE_CAPACITY: usize = 1024;
+
+/// Outcome of preparing a submission for judging
+#[derive(Debug, Clone)]
+pub enum PreparedSubmission {
+    /// An identical submission has been judged before, the verdict is reused
+    Cached(CompilationResponse),
+
+    /// The submission has to be run in the sandbox
+    Run(SandboxInput),
+}
+
 #[derive(Debug, Clone)]
 pub struct Game {
     // The client this game is on (not always host as every client (even clients to games which already have hosts) has their own Game struct)
@@ -62,7 +97,7 @@ pub struct Game {
     pub(crate) sockets: Sockets,
 
     /// Redis pool
-    redis_pool: Pool<RedisConnectionManager>,
+    store: Store,
 
     /// If the game has been shutdown already
     shutdown: bool,
@@ -75,6 +110,18 @@ pub struct Game {
 
     /// List of all tasks to finish before the game ends
     tasks: Vec<GameTask>,
+
+    /// If clients other than the host can send chat messages
+    chat_enabled: bool,
+
+    /// Player id given to the next registered client, the host is always player 0
+    next_player_id: PlayerId,
+
+    /// Verdicts of previous submissions keyed by their submission hash
+    verdict_cache: VerdictCache,
+
+    /// If verdicts are also shared with other shards through redis
+    share_verdict_cache: bool,
 }
 
 impl Game {
@@ -83,8 +130,9 @@ impl Game {
         game_id: String,
         partial_client: PartialClient,
         partial_host: PartialClient,
-        redis_pool: Pool<RedisConnectionManager>,
+        store: Store,
         sockets: Sockets,
+        share_verdict_cache: bool,
     ) -> Game {
         let connected_clients = if is_host { Some(HashMap::new()) } else { None };
         Game {
@@ -93,12 +141,16 @@ impl Game {
             partial_client,
             partial_host,
             connected_clients,
-            redis_pool,
+            store,
             shutdown: false,
             is_started: false,
             sockets,
             public: true,
             tasks: Vec::new(),
+            chat_enabled: true,
+            next_player_id: 1,
+            verdict_cache: VerdictCache::new(VERDICT_CACHE_CAPACITY),
+            share_verdict_cache,
         }
     }
 
@@ -107,6 +159,7 @@ impl Game {
         &mut self,
         available_tasks: Arc<Vec<GameTask>>,
         task_count: usize,
+        disable_chat: bool,
     ) -> Result<(), Box<dyn std::error::Error>> {
         superluminal_perf::begin_event("start game");
         self.is_host()?;
@@ -116,6 +169,7 @@ impl Game {
 
         self.public = false;
         self.is_started = true;
+        self.chat_enabled = !disable_chat;
 
         // Choose a random programming question
         self.tasks = available_tasks
@@ -153,7 +207,8 @@ impl Game {
             .send_global(
                 DefaultModel::new(GameEvent::new(StartGameEvent { task_count })),
                 None,
-                &self.redis_pool,
+                Priority::Normal,
+                &self.store,
             )
             .await;
 
@@ -163,38 +218,69 @@ impl Game {
 
     pub async fn prepare_code_test(
         &mut self,
+        client_id: &Uuid,
         task_index: usize,
-    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        language: Language,
+        code: &str,
+    ) -> Result<PreparedSubmission, Box<dyn std::error::Error>> {
         self.is_host()?;
         let task = self
             .get_task_indexed(task_index)
-            .map_err(|_| ClientError::OutOfRangeTask)?
-            .to_owned();
+            .map_err(|_| ClientError::OutOfRangeTask)?;
+        let submission_hash = task.submission_hash(language, code);
 
-        // Run all public tests, if they all succeed, run the private ones too
-        Ok(task
-            .public_test_cases
-            .iter()
-            .map(|test| test.stdin.to_owned())
-            .collect::<Vec<String>>())
+        // Identical resubmissions are not run again, but still count as a submission
+        if let Some(response) = self.cached_verdict(&submission_hash).await {
+            let response = CompilationResponse {
+                task_index,
+                ..response
+            };
+            self.record_submission(client_id, &response).await?;
+            return Ok(PreparedSubmission::Cached(response));
+        }
+
+        // Run all public and private tests in one pass, the sandbox can not judge the outputs
+        // and runs every input, a failed public test only stops the judging
+        let task = self
+            .get_task_indexed(task_index)
+            .map_err(|_| ClientError::OutOfRangeTask)?;
+        Ok(PreparedSubmission::Run(task.sandbox_input()))
     }
 
     pub async fn run_code_test(
         client_id: &Uuid,
         code: String,
-        gathered_stdin: Vec<String>,
+        language: Language,
+        input: SandboxInput,
     ) -> Result<SandboxResponse, Box<dyn std::error::Error>> {
-        let result = Self::compile_code(client_id, code.clone(), gathered_stdin).await?;
+        let result = Self::compile_code(client_id, code, language, input).await?;
 
         Ok(result)
     }
 
-    /// Compile client code and return result
+    /// Runs code once with custom input, the run is not a submission and is never judged
+    pub async fn run_custom_input(
+        client_id: &Uuid,
+        code: String,
+        language: Language,
+        stdin: String,
+    ) -> Result<SandboxResponse, ClientError<'static>> {
+        let input = SandboxInput {
+            stdin: vec![stdin],
+            limits: vec![TaskLimits::default().into()],
+        };
+
+        Self::compile_code(client_id, code, language, input).await
+    }
+
+    /// Validate the result of a sandbox run against the tests of the task
+    /// and record it as a submission of the client
     pub async fn validate_code_test(
         &mut self,
         client_id: &Uuid,
         task_index: usize,
-        code: String,
+        language: Language,
+        code: &str,
         result: SandboxResponse,
     ) -> Result<CompilationResponse, Box<dyn std::error::Error>> {
         superluminal_perf::begin_event("test code");
@@ -204,198 +290,172 @@ impl Game {
             .map_err(|_| ClientError::OutOfRangeTask)?
             .to_owned();
 
-        let connected_client = if !self.is_host {
-            self.connected_clients
-                .as_mut()
-                .unwrap()
-                .get_mut(client_id)
-                .ok_or(ClientError::ClientDoesNotExist(
-                    "Client does not exist in the game",
-                ))?
-        } else {
-            &mut self.partial_host
-        };
+        let response = Self::judge(&task, task_index, &result);
+        self.cache_verdict(task.submission_hash(language, code), &response)
+            .await;
+        self.record_submission(client_id, &response).await?;
+
+        superluminal_perf::end_event();
+        Ok(response)
+    }
 
+    /// Judges a sandbox run against the tests of a task
+    fn judge(task: &GameTask, task_index: usize, result: &SandboxResponse) -> CompilationResponse {
         if !result.success {
-            return Ok(CompilationResponse {
-                task_index,
-                public_test_progress: vec![],
-                is_done: false,
-                is_done_public_tests: false,
-                is_done_private_tests: false,
-                stderr: result.stderr.join(""),
-            });
+            return CompilationResponse::compile_error(task_index, result.stderr.join(""));
         }
 
         // ! Verify output against public tests
-        let mut public_finished_tests = Vec::new();
-        let mut public_failed_tests = Vec::new();
-        for (i, mut s) in result.stdout.into_iter().enumerate() {
-            if s.ends_with('\n') {
-                s.pop();
-            }
+        let mut public_test_progress = Vec::new();
+        for (i, test) in task.public_test_cases.iter().enumerate() {
+            let (verdict, stdout) = Verdict::from_sandbox(result, i, &test.expected);
+            public_test_progress.push(PublicTestProgress::new(
+                test.id,
+                verdict,
+                stdout,
+                result.stderr.get(i).cloned().unwrap_or_default(),
+                test.expected.to_owned(),
+            ));
 
-            if s == task
-                .public_test_cases
-                .get(i)
-                .ok_or(ClientError::InternalServerError(
-                    "task response length mismatch",
-                ))?
-                .expected
-            {
-                public_finished_tests.push((
-                    task.public_test_cases
-                        .get(i)
-                        .ok_or(ClientError::InternalServerError(
-                            "task response length mismatch",
-                        ))?,
-                    s,
-                ));
-            } else {
-                public_failed_tests.push((
-                    task.public_test_cases
-                        .get(i)
-                        .ok_or(ClientError::InternalServerError(
-                            "task response length mismatch",
-                        ))?,
-                    s,
-                ));
+            if verdict != Verdict::Accepted {
+                return CompilationResponse::new(task_index, verdict, public_test_progress);
             }
         }
 
-        // Check if all public tests succeeded
-        if public_finished_tests.len() != task.public_test_cases.len() {
-            let mut finished_public_tests = Vec::new();
-            public_finished_tests.iter().for_each(|d| {
-                finished_public_tests.push(PublicTestProgress::new_failed(
-                    d.0.id,
-                    d.1.clone(),
-                    d.0.expected.to_owned(),
-                ))
-            });
-            for (index, _) in public_finished_tests.iter().enumerate() {
-                finished_public_tests
-                    .get_mut(index)
-                    .as_mut()
-                    .unwrap()
-                    .make_not_failed();
+        // Verify output against private tests, only the index of the first failure is reported
+        let public_test_count = task.public_test_cases.len();
+        for (i, test) in task.private_test_cases.iter().enumerate() {
+            let (verdict, _) = Verdict::from_sandbox(result, public_test_count + i, &test.expected);
+            if verdict != Verdict::Accepted {
+                return CompilationResponse {
+                    failed_private_test: Some(i),
+                    ..CompilationResponse::new(task_index, verdict, public_test_progress)
+                };
             }
+        }
 
-            // add all the failed tests to the response
-            public_failed_tests.iter().for_each(|d| {
-                finished_public_tests.push(PublicTestProgress::new_failed(
-                    d.0.id,
-                    d.1.clone(),
-                    d.0.expected.to_owned(),
-                ))
-            });
+        CompilationResponse::new(task_index, Verdict::Accepted, public_test_progress)
+    }
 
-            // sort the tests by id
-            finished_public_tests.sort_by(|x, y| x.test_index.cmp(&y.test_index));
+    /// Fetches the verdict of an identical submission, from redis if the cache is shared
+    async fn cached_verdict(&self, submission_hash: &str) -> Option<CompilationResponse> {
+        if let Some(response) = self.verdict_cache.get(submission_hash) {
+            return Some(response.to_owned());
+        }
 
-            return Ok(CompilationResponse {
-                task_index,
-                public_test_progress: finished_public_tests,
-                is_done: false,
-                is_done_public_tests: false,
-                is_done_private_tests: false,
-                stderr: result.stderr.first().unwrap_or(&String::new()).to_string(),
-            });
-        }
-
-        let mut finished_public_tests = Vec::new();
-        public_finished_tests
-            .into_iter()
-            .enumerate()
-            .for_each(|(x, d)| {
-                finished_public_tests.push(PublicTestProgress::new(x, d.1, d.0.expected.to_owned()))
-            });
-
-        // If the public tests succeeded, test against the private test cases
-        // Run all public tests, if they all succeed, run the private ones too
-        let gathered_stdin = task
-            .private_test_cases
-            .iter()
-            .map(|test| test.stdin.to_owned())
-            .collect::<Vec<String>>();
-        let result = Self::compile_code(client_id, code, gathered_stdin).await?;
+        if !self.share_verdict_cache {
+            return None;
+        }
 
-        if !result.success {
-            return Ok(CompilationResponse {
-                task_index,
-                public_test_progress: finished_public_tests,
-                is_done: false,
-                is_done_public_tests: true,
-                is_done_private_tests: false,
-                stderr: result.stderr.join(""),
-            });
-        }
-
-        // Verify output against private tests
-        let mut private_finished_tests = Vec::new();
-        for (i, mut s) in result.stdout.into_iter().enumerate() {
-            if s.ends_with('\n') {
-                s.pop();
-            }
+        let cached = self.store.cached_verdict(submission_hash).await.ok()?;
+        serde_json::from_str(&cached?).ok()
+    }
 
-            if s == task
-                .private_test_cases
-                .get(i)
-                .ok_or(ClientError::InternalServerError(
-                    "task response length mismatch",
-                ))?
-                .expected
-            {
-                private_finished_tests.push(task.private_test_cases.get(i).ok_or(
-                    ClientError::InternalServerError("task response length mismatch"),
-                )?);
+    /// Stores the verdict of a submission, only verdicts that are decided by the code itself are cached.
+    /// Runtime errors and exceeded limits can depend on the load of the sandbox
+    async fn cache_verdict(&mut self, submission_hash: String, response: &CompilationResponse) {
+        if !response.verdict.is_deterministic() {
+            return;
+        }
+
+        if self.share_verdict_cache {
+            let res = self
+                .store
+                .cache_verdict(
+                    &submission_hash,
+                    serde_json::to_string(response).unwrap(),
+                    VERDICT_CACHE_TTL,
+                )
+                .await;
+            if let Err(e) = res {
+                error!("Could not share verdict with other shards: {}", e);
             }
         }
 
-        // Check if all public tests succeeded
-        if private_finished_tests.len() != task.private_test_cases.len() {
-            return Ok(CompilationResponse {
-                task_index,
-                public_test_progress: finished_public_tests,
-                is_done: false,
-                is_done_public_tests: true,
-                is_done_private_tests: false,
-                stderr: result.stderr.first().unwrap_or(&String::new()).to_string(),
-            });
+        self.verdict_cache
+            .insert(submission_hash, response.to_owned());
+    }
+
+    /// Counts a judged submission for the client, an accepted submission finishes the task
+    async fn record_submission(
+        &mut self,
+        client_id: &Uuid,
+        response: &CompilationResponse,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let task_index = response.task_index;
+        let task = self
+            .get_task_indexed(task_index)
+            .map_err(|_| ClientError::OutOfRangeTask)?
+            .to_owned();
+
+        let connected_client = if *client_id != self.partial_host.id {
+            self.connected_clients
+                .as_mut()
+                .unwrap()
+                .get_mut(client_id)
+                .ok_or(ClientError::ClientDoesNotExist(
+                    "Client does not exist in the game",
+                ))?
+        } else {
+            &mut self.partial_host
+        };
+
+        *connected_client.submissions.entry(task_index).or_insert(0) += 1;
+        if response.verdict != Verdict::Accepted {
+            return Ok(());
         }
 
-        // Set the task as finished
-        connected_client
+        // Set the task as finished, the other clients are only told the first time
+        let already_finished = connected_client
             .task_progress
             .as_mut()
             .unwrap()
-            .insert(task_index, true);
+            .insert(task_index, true)
+            == Some(true);
+        if already_finished {
+            return Ok(());
+        }
+        let player_id = connected_client.player_id;
 
-        // Send global event, client has succeeded with task
-        let _ = self
-            .send_global(
-                DefaultModel::new(GameEvent::new(TaskFinishedGameEvent { task, task_index, client_id: *client_id })),
-                Some(&[client_id]),
-                &self.redis_pool,
-            )
-            .await;
+        // Send event to everyone else in the game, client has succeeded with task.
+        // Each client gets the statement in its own locale
+        for client in self
+            .connected_clients
+            .as_ref()
+            .unwrap()
+            .values()
+            .chain(std::iter::once(&self.partial_host))
+            .filter(|client| client.id != *client_id)
+        {
+            let event = TaskFinishedGameEvent {
+                task: TaskStatementView::localized(&task, client.locale.as_deref()),
+                task_index,
+                player_id,
+            };
+            if let Err(e) = client
+                .send_message(
+                    DefaultModel::new(GameEvent::new(event)),
+                    Priority::Low,
+                    &self.store,
+                )
+                .await
+            {
+                error!(
+                    "Failed to send task finished event to client with id {}, error {}",
+                    client.id, e
+                );
+            }
+        }
 
-        superluminal_perf::end_event();
-        Ok(CompilationResponse {
-            task_index,
-            public_test_progress: finished_public_tests,
-            is_done: true,
-            is_done_public_tests: true,
-            is_done_private_tests: true,
-            stderr: String::new(),
-        })
+        Ok(())
     }
 
     /// Compile client code and return result
     async fn compile_code(
         client_id: &Uuid,
         code: String,
-        stdin: Vec<String>,
+        language: Language,
+        input: SandboxInput,
     ) -> Result<SandboxResponse, ClientError<'static>> {
         let mut client = SandboxServiceClient::connect("http://127.0.0.1:50051")
             .await
@@ -403,8 +463,9 @@ impl Game {
         let request = tonic::Request::new(SandboxRequest {
             user_id: client_id.to_string(),
             code,
-            stdin,
-            language: sandbox::Language::Rust as i32,
+            stdin: input.stdin,
+            language: sandbox::Language::from(language) as i32,
+            limits: input.limits,
         });
 
         Ok((client.compile(request).await)
@@ -422,51 +483,22 @@ impl Game {
     }
 
     /// Register a new client with the game
-    pub async fn register(&mut self, partial_client: PartialClient) -> Result<(), ()> {
+    ///
+    /// Returns the client with its player id and nickname within the game, the nickname has a number
+    /// appended to it if the nickname (or a confusable one) is already used by someone else in the game.
+    /// Nobody is notified yet, the caller announces the client with `announce_registration`
+    /// after releasing the host socket
+    pub fn register(&mut self, mut partial_client: PartialClient) -> Result<PartialClient, ()> {
         superluminal_perf::begin_event("register client");
         // Cancel if user is not game host
         if !self.is_host || !self.public {
+            superluminal_perf::end_event();
             return Err(());
         }
 
-        // Send existing clients to the newly connected client
-        for clients in self.connected_clients.as_ref().unwrap().iter() {
-            let _ = partial_client
-                .send_message(
-                    DefaultModel::new(GameEvent::new(ConnectedClientGameEvent {
-                        game_id: self.game_id.clone(),
-                        client_id: *clients.0,
-                        nickname: clients.1.nickname.clone(),
-                    })),
-                    &self.redis_pool,
-                )
-                .await;
-        }
-
-        // Send the host to the newly connected client
-        let _ = partial_client
-            .send_message(
-                DefaultModel::new(GameEvent::new(ConnectedClientGameEvent {
-                    game_id: self.game_id.clone(),
-                    client_id: self.partial_host.id,
-                    nickname: self.partial_host.nickname.clone(),
-                })),
-                &self.redis_pool,
-            )
-            .await;
-
-        // Send the new client to all connected clients
-        let _ = self
-            .send_global(
-                DefaultModel::new(GameEvent::new(ConnectedClientGameEvent {
-                    game_id: self.game_id.clone(),
-                    client_id: partial_client.id,
-                    nickname: partial_client.nickname.clone(),
-                })),
-                None,
-                &self.redis_pool,
-            )
-            .await;
+        partial_client.nickname = self.unique_nickname(&partial_client.nickname);
+        partial_client.player_id = self.next_player_id;
+        self.next_player_id += 1;
 
         trace!(
             "Registering client {} in game {}",
@@ -477,37 +509,210 @@ impl Game {
         self.connected_clients
             .as_mut()
             .unwrap()
-            .insert(partial_client.id, partial_client);
+            .insert(partial_client.id, partial_client.clone());
 
         superluminal_perf::end_event();
-        Ok(())
+        Ok(partial_client)
+    }
+
+    /// Sends everyone already in the game to a newly registered client, and the client to everyone else.
+    /// `participants` are taken from the game after the client was registered
+    pub async fn announce_registration(
+        game_id: &str,
+        client: &PartialClient,
+        participants: &[PartialClient],
+        store: &Store,
+    ) {
+        let connected = |participant: &PartialClient| {
+            DefaultModel::new(GameEvent::new(ConnectedClientGameEvent {
+                game_id: game_id.to_owned(),
+                player_id: participant.player_id,
+                nickname: participant.nickname.clone(),
+            }))
+        };
+
+        for participant in participants.iter().filter(|p| p.id != client.id) {
+            let _ = client
+                .send_message(connected(participant), Priority::Normal, store)
+                .await;
+            if let Err(e) = participant
+                .send_message(connected(client), Priority::Normal, store)
+                .await
+            {
+                error!(
+                    "Failed to send connected client event to client with id {}, error {}",
+                    participant.id, e
+                );
+            }
+        }
     }
 
-    /// Unregister a client from the game
-    pub async fn unregister(&mut self, client_id: &Uuid) {
-        superluminal_perf::begin_event("unregister client");
+    /// Appends a number to the nickname until it does not collide with anyone in the game,
+    /// joining through the host is the only way into a game as games on other shards can not be joined.
+    /// The nickname is shortened if needed so it stays within the maximum nickname length
+    fn unique_nickname(&self, nickname: &str) -> String {
+        let taken = self
+            .connected_clients
+            .as_ref()
+            .unwrap()
+            .values()
+            .chain(std::iter::once(&self.partial_host))
+            .map(|client| nickname::skeleton(&client.nickname))
+            .collect::<Vec<String>>();
+
+        let mut unique = nickname.to_string();
+        let mut suffix = 2;
+        while taken.contains(&nickname::skeleton(&unique)) {
+            let suffix_text = suffix.to_string();
+            let base = nickname
+                .chars()
+                .take(nickname::MAX_NICKNAME_LENGTH - suffix_text.len())
+                .collect::<String>();
+            unique = format!("{}{}", base.trim_end(), suffix_text);
+            suffix += 1;
+        }
+
+        unique
+    }
+
+    /// Unregister a client from the game, returns its player id if it was in the game.
+    /// The caller tells the remaining participants with `announce_unregistration`
+    /// after releasing the host socket
+    pub fn unregister(&mut self, client_id: &Uuid) -> Option<PlayerId> {
         // Cancel if user is not game host
-        if self.is_host
-            && self
-                .connected_clients
+        if !self.is_host {
+            return None;
+        }
+
+        self.connected_clients
+            .as_mut()
+            .unwrap()
+            .remove(client_id)
+            .map(|client| client.player_id)
+    }
+
+    /// Sends the disconnected client event to everyone left in the game
+    pub async fn announce_unregistration(
+        game_id: &str,
+        player_id: PlayerId,
+        participants: &[PartialClient],
+        store: &Store,
+    ) {
+        let message = DefaultModel::new(GameEvent::new(DisconnectedClientGameEvent {
+            game_id: game_id.to_owned(),
+            player_id,
+        }));
+        for participant in participants {
+            if let Err(e) = participant
+                .send_message(message.clone(), Priority::Normal, store)
+                .await
+            {
+                error!(
+                    "Failed to send disconnected client event to client with id {}, error {}",
+                    participant.id, e
+                );
+            }
+        }
+    }
+
+    /// Builds the chat message a client sends to everyone in the game,
+    /// the caller broadcasts it to `participants` after releasing the host socket
+    pub fn chat_message(
+        &mut self,
+        client_id: &Uuid,
+        message: String,
+    ) -> Result<ChatMessageGameEvent, ClientError<'static>> {
+        if !self.is_host {
+            return Err(ClientError::NotGameHost("Client is not the game host"));
+        }
+
+        let message = message.trim().to_string();
+        if message.is_empty() {
+            return Err(ClientError::InvalidMessage("Chat message is empty"));
+        } else if message.chars().count() > MAX_CHAT_MESSAGE_LENGTH {
+            return Err(ClientError::ChatMessageTooLong);
+        }
+
+        let is_host = *client_id == self.partial_host.id;
+        if !is_host && !self.chat_enabled {
+            return Err(ClientError::ChatDisabled);
+        }
+
+        let sender = if is_host {
+            &mut self.partial_host
+        } else {
+            self.connected_clients
                 .as_mut()
                 .unwrap()
-                .remove(client_id)
-                .is_some()
-        {
-            // Send the disconnected client event to all connected clients
-            let _ = self
-                .send_global(
-                    DefaultModel::new(GameEvent::new(DisconnectedClientGameEvent {
-                        game_id: self.game_id.clone(),
-                        client_id: *client_id,
-                    })),
-                    None,
-                    &self.redis_pool,
-                )
-                .await;
+                .get_mut(client_id)
+                .ok_or(ClientError::ClientDoesNotExist(
+                    "Client does not exist in the game",
+                ))?
+        };
+
+        if sender.muted {
+            return Err(ClientError::ChatMuted);
         }
-        superluminal_perf::end_event();
+
+        // Only keep the messages sent within the rate limit window
+        let now = Instant::now();
+        while let Some(sent_at) = sender.chat_history.front() {
+            if now.duration_since(*sent_at) > CHAT_RATE_LIMIT_WINDOW {
+                sender.chat_history.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if sender.chat_history.len() >= CHAT_RATE_LIMIT_MESSAGES {
+            return Err(ClientError::ChatRateLimited);
+        }
+        sender.chat_history.push_back(now);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        Ok(ChatMessageGameEvent {
+            game_id: self.game_id.clone(),
+            player_id: sender.player_id,
+            nickname: sender.nickname.clone(),
+            message,
+            timestamp,
+        })
+    }
+
+    /// Every client in the game including the host
+    pub fn participants(&self) -> Vec<PartialClient> {
+        self.connected_clients
+            .iter()
+            .flat_map(|clients| clients.values())
+            .chain(std::iter::once(&self.partial_host))
+            .cloned()
+            .collect()
+    }
+
+    /// Mutes or unmutes a player in the game chat, the host can not be muted
+    pub fn set_muted(
+        &mut self,
+        player_id: PlayerId,
+        muted: bool,
+    ) -> Result<(), ClientError<'static>> {
+        if !self.is_host {
+            return Err(ClientError::NotGameHost("Client is not the game host"));
+        }
+
+        self.connected_clients
+            .as_mut()
+            .unwrap()
+            .values_mut()
+            .find(|client| client.player_id == player_id)
+            .ok_or(ClientError::ClientDoesNotExist(
+                "Player does not exist in the game",
+            ))?
+            .muted = muted;
+
+        Ok(())
     }
 
     /// Send a message to all connected clients in the game
@@ -515,7 +720,8 @@ impl Game {
         &self,
         message: DefaultModel<T>,
         skip_client_ids: Option<&[&Uuid]>,
-        redis_pool: &Pool<RedisConnectionManager>,
+        priority: Priority,
+        store: &Store,
     ) -> Result<(), Box<dyn std::error::Error>>
     where
         T: Serialize + Deserialize<'a> + Clone,
@@ -537,7 +743,11 @@ impl Game {
                 }
             }
 
-            if let Err(e) = client.1.send_message(message.clone(), redis_pool).await {
+            if let Err(e) = client
+                .1
+                .send_message(message.clone(), priority, store)
+                .await
+            {
                 error!(
                     "Failed to send global message to client with id {}, error {}",
                     client.0, e
@@ -554,7 +764,9 @@ impl Game {
         }
 
         if !should_skip_host {
-            self.partial_host.send_message(message, redis_pool).await?;
+            self.partial_host
+                .send_message(message, priority, store)
+                .await?;
         }
 
         superluminal_perf::end_event();
@@ -574,8 +786,11 @@ impl Game {
             let partial = client.1;
             if let Err(e) = partial
                 .send_message(
-                    DefaultModel::new(GameEvent::new(ShutdownGameEvent {})),
-                    &self.redis_pool,
+                    DefaultModel::new(GameEvent::new(ShutdownGameEvent {
+                        reconnect_after_ms: None,
+                    })),
+                    Priority::Normal,
+                    &self.store,
                 )
                 .await
             {
@@ -586,9 +801,8 @@ impl Game {
         self.connected_clients.as_mut().unwrap().clear();
         self.shutdown = true;
 
-        // Deleting game from redis
-        let mut conn = self.redis_pool.get()?;
-        let _: () = conn.del(format!("GAME:{}", self.game_id))?;
+        // Deleting game from the state store
+        self.store.delete_game(&self.game_id).await?;
 
         // Send final goodbye to the host
         self.partial_host
@@ -597,13 +811,102 @@ impl Game {
                     Some(ShutdownResponse { success: true }),
                     models::ResponseOpCode::Shutdown,
                 )),
-                &self.redis_pool,
+                Priority::Normal,
+                &self.store,
             )
             .await?;
         superluminal_perf::end_event();
         Ok(())
     }
 
+    /// Ends the game because this shard is shutting down, the clients are told when to reconnect.
+    /// Games hosted on other shards only notify the local client, the host notifies its own clients
+    pub async fn shutdown_shard(
+        &mut self,
+        reconnect_after: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let event = || {
+            DefaultModel::new(GameEvent::new(ShutdownGameEvent {
+                reconnect_after_ms: Some(reconnect_after.as_millis() as u64),
+            }))
+        };
+
+        if !self.is_host {
+            if !self.partial_host.is_local {
+                self.partial_client
+                    .send_message(event(), Priority::Normal, &self.store)
+                    .await?;
+            }
+            return Ok(());
+        }
+
+        for partial in self
+            .connected_clients
+            .iter()
+            .flat_map(|clients| clients.values())
+        {
+            if let Err(e) = partial
+                .send_message(event(), Priority::Normal, &self.store)
+                .await
+            {
+                error!("Failed to send shutdown event to client {}", e);
+            }
+        }
+
+        self.connected_clients.as_mut().unwrap().clear();
+        self.shutdown = true;
+
+        // Deleting game from the state store
+        self.store.delete_game(&self.game_id).await?;
+
+        self.partial_host
+            .send_message(event(), Priority::Normal, &self.store)
+            .await
+    }
+
+    /// Leaves the game, or shuts it down for every client if the client is the host.
+    /// Has to be awaited when the client leaves, dropping the game does not notify anyone
+    pub async fn close(mut self) {
+        superluminal_perf::begin_event("closing game");
+        if self.is_host {
+            // Games ended by a shard shutdown already notified their clients
+            if !self.shutdown {
+                info!("Shutting down a game");
+                if let Err(e) = self.shutdown().await {
+                    error!("Failed to shut down game {}: {}", self.game_id, e);
+                }
+            }
+        } else if self.partial_host.is_local {
+            info!("Leaving game, the host is local");
+            // The host socket is released before the remaining participants are told
+            let unregistered =
+                self.sockets
+                    .get_mut(&self.partial_host.id)
+                    .and_then(|mut host_client| {
+                        let game = host_client.game.as_mut()?;
+                        let player_id = game.unregister(&self.partial_client.id)?;
+                        Some((player_id, game.participants()))
+                    });
+            if let Some((player_id, participants)) = unregistered {
+                Game::announce_unregistration(&self.game_id, player_id, &participants, &self.store)
+                    .await;
+            }
+
+            let _ = self
+                .partial_client
+                .send_message(
+                    DefaultModel::new(Response::new(
+                        Some(LeaveResponse { success: true }),
+                        ResponseOpCode::Leave,
+                    )),
+                    Priority::Normal,
+                    &self.store,
+                )
+                .await;
+        }
+        superluminal_perf::end_event();
+    }
+
     fn is_host(&self) -> Result<(), Box<dyn std::error::Error>> {
         if !self.is_host {
             Err(Box::new(ClientError::NotGameHost(
@@ -615,31 +918,197 @@ impl Game {
     }
 }
 
-impl Drop for Game {
-    fn drop(&mut self) {
-        superluminal_perf::begin_event("dropping game");
-        info!("Dropping a game object");
-        if self.is_host {
-            info!("Shutting down a game");
-            let _ = futures::executor::block_on(self.shutdown());
-            let mut conn = self.redis_pool.get().unwrap();
-            let _: () = conn.del(format!("GAME:{}", self.game_id)).unwrap();
-        } else if self.partial_host.is_local {
-            info!("Leaving game, the host is local");
-            if let Some(host_client) = &mut self.sockets.get_mut(&self.partial_host.id) {
-                if let Some(game) = &mut host_client.game {
-                    futures::executor::block_on(game.unregister(&self.partial_client.id));
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
 
-            let _ = futures::executor::block_on(self.partial_client.send_message(
-                DefaultModel::new(Response::new(
-                    Some(LeaveResponse { success: true }),
-                    ResponseOpCode::Leave,
-                )),
-                &self.redis_pool,
-            ));
+    use crate::service::{
+        state_store::memory::MemoryStateStore,
+        task_loader,
+        websocket::{
+            outbound::{OutboundQueue, OutboundReceiver},
+            protocol::Protocol,
+        },
+    };
+
+    use super::{sandbox::ExecutionStatus, *};
+
+    const TASKS: &str = r#"
+        [[tasks]]
+        task_id = "248fa5e0-a3ac-4de0-a077-307c80d12126"
+        statement = { body = "Echo the input" }
+
+        [[tasks.public_test_cases]]
+        stdin = "a"
+        expected = "a"
+        id = 0
+
+        [[tasks.private_test_cases]]
+        stdin = "b"
+        expected = "b"
+        id = 0
+    "#;
+
+    fn remote_client(nickname: &str) -> PartialClient {
+        PartialClient::new(
+            Uuid::new_v4(),
+            nickname.to_string(),
+            "shard".to_string(),
+            false,
+            None,
+            Protocol::DEFAULT,
+        )
+    }
+
+    fn local_client(nickname: &str) -> (PartialClient, OutboundReceiver) {
+        let (queue, messages) = OutboundQueue::new();
+        let client = PartialClient::new(
+            Uuid::new_v4(),
+            nickname.to_string(),
+            "shard".to_string(),
+            true,
+            Some(queue),
+            Protocol::DEFAULT,
+        );
+        (client, messages)
+    }
+
+    fn count_messages(messages: &mut OutboundReceiver) -> usize {
+        let mut count = 0;
+        while messages.try_recv().is_some() {
+            count += 1;
         }
-        superluminal_perf::end_event();
+        count
+    }
+
+    fn hosted_game(host_nickname: &str) -> Game {
+        game_hosted_by(remote_client(host_nickname))
+    }
+
+    fn game_hosted_by(host: PartialClient) -> Game {
+        Game::new(
+            true,
+            "game".to_string(),
+            host.clone(),
+            host,
+            Arc::new(MemoryStateStore::default()),
+            Arc::new(DashMap::new()),
+            false,
+        )
+    }
+
+    #[test]
+    fn register_deduplicates_confusable_nicknames() {
+        let mut game = hosted_game("john");
+
+        let first = game.register(remote_client("john")).unwrap();
+        let second = game.register(remote_client("jоhn")).unwrap();
+        let third = game.register(remote_client("mall")).unwrap();
+        let fourth = game.register(remote_client("mail")).unwrap();
+
+        assert_eq!(first.nickname, "john2");
+        assert_eq!(second.nickname, "jоhn3");
+        assert_eq!(third.nickname, "mall");
+        assert_eq!(fourth.nickname, "mail");
+        assert_eq!(
+            (first.player_id, second.player_id, third.player_id),
+            (1, 2, 3)
+        );
+    }
+
+    #[test]
+    fn deduplicated_nicknames_stay_within_the_maximum_length() {
+        let nickname = "a".repeat(nickname::MAX_NICKNAME_LENGTH);
+        let mut game = hosted_game(&nickname);
+
+        let first = game.register(remote_client(&nickname)).unwrap().nickname;
+        let second = game.register(remote_client(&nickname)).unwrap().nickname;
+
+        assert_eq!(first, format!("{}2", &nickname[1..]));
+        assert_eq!(second, format!("{}3", &nickname[1..]));
+        assert!(first.chars().count() <= nickname::MAX_NICKNAME_LENGTH);
+    }
+
+    /// Starts a game with a single task, returns the game, the id of the
+    /// player submitting code and the messages received by the host
+    async fn started_game() -> (Game, Uuid, OutboundReceiver) {
+        let (host, mut host_messages) = local_client("host");
+        let mut game = game_hosted_by(host);
+        let solver = remote_client("solver");
+        let solver_id = solver.id;
+        game.register(solver).unwrap();
+        game.start(Arc::new(task_loader::load_tasks(TASKS)), 1, false)
+            .await
+            .unwrap();
+        count_messages(&mut host_messages);
+
+        (game, solver_id, host_messages)
+    }
+
+    fn sandbox_result(stdout: &[&str], statuses: &[ExecutionStatus]) -> SandboxResponse {
+        SandboxResponse {
+            success: true,
+            stdout: stdout.iter().map(|s| s.to_string()).collect(),
+            stderr: vec![String::new(); stdout.len()],
+            statuses: statuses.iter().map(|status| *status as i32).collect(),
+            exit_codes: vec![0; stdout.len()],
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_accepted_submission_does_not_finish_the_task_again() {
+        let (mut game, solver_id, mut host_messages) = started_game().await;
+
+        let accepted = sandbox_result(&["a", "b"], &[]);
+        let response = game
+            .validate_code_test(&solver_id, 0, Language::Rust, "code", accepted)
+            .await
+            .unwrap();
+        assert_eq!(response.verdict, Verdict::Accepted);
+        assert_eq!(count_messages(&mut host_messages), 1);
+
+        match game
+            .prepare_code_test(&solver_id, 0, Language::Rust, "code")
+            .await
+            .unwrap()
+        {
+            PreparedSubmission::Cached(response) => assert_eq!(response.verdict, Verdict::Accepted),
+            PreparedSubmission::Run(_) => panic!("accepted submission was not cached"),
+        }
+        assert_eq!(count_messages(&mut host_messages), 0);
+        let solver = &game.connected_clients.as_ref().unwrap()[&solver_id];
+        assert_eq!(solver.submissions.get(&0), Some(&2));
+    }
+
+    #[tokio::test]
+    async fn only_deterministic_verdicts_are_cached() {
+        let (mut game, solver_id, _host_messages) = started_game().await;
+
+        let time_limit = sandbox_result(
+            &["a", ""],
+            &[ExecutionStatus::Ok, ExecutionStatus::TimeLimitExceeded],
+        );
+        let response = game
+            .validate_code_test(&solver_id, 0, Language::Rust, "slow", time_limit)
+            .await
+            .unwrap();
+        assert_eq!(response.verdict, Verdict::TimeLimit);
+        assert!(matches!(
+            game.prepare_code_test(&solver_id, 0, Language::Rust, "slow")
+                .await
+                .unwrap(),
+            PreparedSubmission::Run(_)
+        ));
+
+        let wrong_answer = sandbox_result(&["a", "c"], &[]);
+        game.validate_code_test(&solver_id, 0, Language::Rust, "wrong", wrong_answer)
+            .await
+            .unwrap();
+        assert!(matches!(
+            game.prepare_code_test(&solver_id, 0, Language::Rust, "wrong")
+                .await
+                .unwrap(),
+            PreparedSubmission::Cached(_)
+        ));
     }
 }
@@ -0,0 +1,19 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::sandbox;
+
+/// Programming languages that clients can submit code in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Language {
+    #[default]
+    Rust,
+}
+
+impl From<Language> for sandbox::Language {
+    fn from(language: Language) -> Self {
+        match language {
+            Language::Rust => sandbox::Language::Rust,
+        }
+    }
+}
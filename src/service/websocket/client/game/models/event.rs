@@ -1,7 +1,10 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use strum::EnumIter;
 
 use crate::service::websocket::client::models::{OpCode, OpCodeFetcher};
 
+pub mod chat_message;
 pub mod shutdown;
 pub mod start;
 pub mod task;
@@ -13,7 +16,7 @@ pub mod connected_client;
 pub mod disconnected_client;
 
 // Models for games
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GameEvent<T> {
     event: T,
     op: GameEventOpCode,
@@ -31,7 +34,9 @@ impl<T> GameEvent<T> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema, EnumIter,
+)]
 pub enum GameEventOpCode {
     /// Event triggered when game ends or host decides to force shutdown it
     Shutdown,
@@ -45,6 +50,9 @@ pub enum GameEventOpCode {
     ConnectedClient,
     /// Event sent to everyone in a game when an existing client is disconnected (not sent to the client itself)
     DisconnectedClient,
+
+    /// Chat message sent by a client in the game, sent to everyone including the sender
+    ChatMessage,
 }
 
 pub trait GameEventOpCodeFetcher {
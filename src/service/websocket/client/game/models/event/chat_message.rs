@@ -0,0 +1,24 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::service::websocket::client::game::PlayerId;
+
+use super::{GameEventOpCode, GameEventOpCodeFetcher};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChatMessageGameEvent {
+    pub(crate) game_id: String,
+    pub(crate) player_id: PlayerId,
+    pub(crate) nickname: String,
+    pub(crate) message: String,
+
+    /// Milliseconds since the unix epoch when the host received the message
+    pub(crate) timestamp: u64,
+}
+
+impl GameEventOpCodeFetcher for ChatMessageGameEvent {
+    #[inline]
+    fn op_code() -> GameEventOpCode {
+        GameEventOpCode::ChatMessage
+    }
+}
@@ -1,12 +1,14 @@
-use serde::{Serialize, Deserialize};
-use uuid::Uuid;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::service::websocket::client::game::PlayerId;
 
 use super::{GameEventOpCode, GameEventOpCodeFetcher};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ConnectedClientGameEvent {
     pub(crate) game_id: String,
-    pub(crate) client_id: Uuid,
+    pub(crate) player_id: PlayerId,
     pub(crate) nickname: String,
 }
 
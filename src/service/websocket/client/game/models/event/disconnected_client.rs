@@ -1,14 +1,14 @@
-use serde::{Serialize, Deserialize};
-use uuid::Uuid;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::service::websocket::client::game::PlayerId;
 
 use super::{GameEventOpCode, GameEventOpCodeFetcher};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DisconnectedClientGameEvent {
     pub(crate) game_id: String,
-
-    // ! Todo: Replace client id with a name, we don't want to share client ids to other connected clients
-    pub(crate) client_id: Uuid,
+    pub(crate) player_id: PlayerId,
 }
 
 impl GameEventOpCodeFetcher for DisconnectedClientGameEvent {
@@ -1,9 +1,15 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::{GameEventOpCode, GameEventOpCodeFetcher};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ShutdownGameEvent {}
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ShutdownGameEvent {
+    /// Set if the shard of the game is shutting down, clients should reconnect after this delay
+    /// and will then be served by another shard
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) reconnect_after_ms: Option<u64>,
+}
 
 impl GameEventOpCodeFetcher for ShutdownGameEvent {
     #[inline]
@@ -1,12 +1,13 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::service::websocket::client::game::task::GameTask;
+use crate::service::websocket::client::game::task::view::PublicTaskView;
 
 use super::{GameEventOpCode, GameEventOpCodeFetcher};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TaskGameEvent {
-    pub(crate) task: GameTask,
+    pub(crate) task: PublicTaskView,
 }
 
 impl GameEventOpCodeFetcher for TaskGameEvent {
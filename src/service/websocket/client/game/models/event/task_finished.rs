@@ -1,15 +1,15 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use uuid::Uuid;
 
-use crate::service::websocket::client::game::task::GameTask;
+use crate::service::websocket::client::game::{task::view::TaskStatementView, PlayerId};
 
 use super::{GameEventOpCode, GameEventOpCodeFetcher};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TaskFinishedGameEvent {
-    pub(crate) task: GameTask,
+    pub(crate) task: TaskStatementView,
     pub(crate) task_index: usize,
-    pub(crate) client_id: Uuid,
+    pub(crate) player_id: PlayerId,
 }
 
 impl GameEventOpCodeFetcher for TaskFinishedGameEvent {
@@ -1,49 +1,66 @@
 use std::sync::Arc;
 
-use r2d2::Pool;
-use redis::Commands;
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
+use strum::EnumIter;
 use uuid::Uuid;
 
 use crate::service::{
-    redis_pool::RedisConnectionManager,
+    settings::Settings,
+    state_store::{StateError, Store, StoredGame},
     websocket::client::{
         error::ClientError,
-        game::{partial_client::PartialClient, redis_game::RedisGame, task::GameTask, Game},
+        game::{
+            partial_client::PartialClient,
+            redis_game::RedisGame,
+            task::{view::PublicTaskView, GameTask},
+            Game, PreparedSubmission,
+        },
         models::{DefaultModel, OpCode, OpCodeFetcher},
+        nickname::NicknameRejection,
     },
+    websocket::outbound::Priority,
     Sockets,
 };
 
 use self::{
-    compile::CompileRequest, create::CreateRequest, exists::ExistsRequest,
-    identify::IdentifyRequest, join::JoinRequest, start::StartRequest, task::TaskRequest,
+    chat::ChatRequest, compile::CompileRequest, create::CreateRequest, exists::ExistsRequest,
+    identify::IdentifyRequest, join::JoinRequest, mute::MuteRequest, run::RunRequest,
+    start::StartRequest, task::TaskRequest,
 };
 
 use super::{
     response::{
-        create::CreateResponse, exists::ExistsResponse, identify::IdentifyResponse,
-        join::JoinResponse, ping::PingResponse, task::TaskResponse,
+        chat::ChatResponse, compile::CompilationResponse, create::CreateResponse,
+        exists::ExistsResponse, identify::IdentifyResponse, join::JoinResponse, mute::MuteResponse,
+        ping::PingResponse, run::RunResponse, task::TaskResponse,
     },
-    Response, ResponseOpCode,
+    GameEvent, Response, ResponseOpCode,
 };
 
+pub mod chat;
 pub mod compile;
 pub mod create;
 pub mod exists;
 pub mod identify;
 pub mod join;
 pub mod leave;
+pub mod mute;
 pub mod ping;
+pub mod run;
 pub mod start;
 pub mod task;
 
 // Models for requests
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Request {
     d: Option<Value>,
-    op: RequestOpCode,
+    pub(crate) op: RequestOpCode,
+
+    /// Optional id chosen by the client, echoed on the response and errors caused by this request
+    #[serde(default, alias = "request_id")]
+    pub(crate) nonce: Option<String>,
 }
 
 impl Request {
@@ -51,242 +68,243 @@ impl Request {
         self,
         client_id: Uuid,
         sockets: &Sockets,
-        redis_pool: Pool<RedisConnectionManager>,
+        store: Store,
         available_tasks: Arc<Vec<GameTask>>,
+        settings: Arc<Settings>,
         shard_id: &str,
     ) -> Result<(), ClientError<'a>> {
+        let nonce = self.nonce.clone();
         match self.op {
             RequestOpCode::Join => {
-                if self.d.is_none() {
-                    return Err(ClientError::NoDataWithOpCode(
-                        "No data was sent with join request",
-                    ));
-                }
-
                 // Parse the join request
-                let join_game: JoinRequest = serde_json::from_value(self.d.unwrap())
-                    .map_err(|_| ClientError::ParsingError)?;
+                let join_game: JoinRequest = self.data()?;
 
                 let client_write_channel;
-                let mut conn;
-                let game;
+                let client_protocol;
+                let client_locale;
                 let nickname;
                 {
                     let client = sockets.get(&client_id).unwrap();
                     client_write_channel = client.send_channel.clone();
+                    client_protocol = client.protocol;
+                    client_locale = client.locale.clone();
 
                     // Check if user is already in a game
                     if client.game.is_some() {
-                        let _ = client
-                            .send_error(ClientError::AlreadyInGame("Client is already in a game"))
-                            .await;
                         return Err(ClientError::AlreadyInGame("Client is already in a game"));
                     }
 
                     // Check if the client has identified itself
                     if client.nickname.is_none() {
-                        let _ = client.send_error(ClientError::ClientNotIdentified).await;
                         return Err(ClientError::ClientNotIdentified);
                     } else {
                         nickname = client.nickname.as_ref().unwrap().clone();
                     }
 
-                    // get a redis connection from the pool
-                    conn = match redis_pool.get() {
-                        Ok(c) => c,
-                        Err(_) => {
-                            let _ = client
-                                .send_error(ClientError::InternalServerError(
-                                    "Internal Server Error",
-                                ))
-                                .await;
-                            return Err(ClientError::InternalServerError("Internal Server Error"));
-                        }
-                    };
-
-                    // Try to fetch the game from redis
-                    let redis_game: redis::RedisResult<String> =
-                        conn.get(format!("GAME:{}", join_game.game_id));
-                    game = match redis_game {
-                        Ok(game) => game,
-                        Err(_) => {
-                            error!("No game was found");
-                            let _ = client.send_error(ClientError::NoGameWasFound).await;
-                            return Err(ClientError::NoGameWasFound);
-                        }
-                    };
-
                     drop(client);
                 }
 
+                // Try to fetch the game from the state store
+                let game = match store.lookup_game(&join_game.game_id).await {
+                    Ok(Some(game)) => game,
+                    Ok(None) => {
+                        error!("No game was found");
+                        return Err(ClientError::NoGameWasFound);
+                    }
+                    Err(e) => {
+                        error!("Failed to look up game: {}", e);
+                        return Err(ClientError::InternalServerError("Internal cache error"));
+                    }
+                };
+
                 // Check if the game has been initialized
                 // If it'sockets not, set self as host and join game
-                if game == String::new() {
-                    // Serialize game object data
-                    let redis_game = RedisGame {
-                        shard_id: (*shard_id).to_string(),
-                        host_id: client_id,
-                    };
-                    let serialized_redis_game = serde_json::to_string(&redis_game).unwrap();
-
-                    // Register as host
-                    let _: () = conn
-                        .set(format!("GAME:{}", join_game.game_id), serialized_redis_game)
-                        .map_err(|_| ClientError::InternalServerError("Internal cache error"))?;
-
-                    let mut client = sockets.get_mut(&client_id).unwrap();
-                    client.game = Some(Game::new(
-                        true,
-                        join_game.game_id.clone(),
-                        PartialClient::new(
-                            client.id,
-                            client.nickname.as_ref().unwrap().to_owned(),
-                            redis_game.shard_id.clone(),
-                            true,
-                            Some(client.send_channel.clone()),
-                        ),
-                        PartialClient::new(
-                            client.id,
-                            client.nickname.as_ref().unwrap().to_owned(),
-                            redis_game.shard_id,
-                            true,
-                            Some(client.send_channel.clone()),
-                        ),
-                        redis_pool,
-                        sockets.clone(),
-                    ));
+                match game {
+                    StoredGame::Created => {
+                        // Register as host
+                        let redis_game = RedisGame {
+                            shard_id: (*shard_id).to_string(),
+                            host_id: client_id,
+                        };
+                        store
+                            .host_game(&join_game.game_id, &redis_game)
+                            .await
+                            .map_err(|_| {
+                                ClientError::InternalServerError("Internal cache error")
+                            })?;
 
-                    client
-                        .send_model(DefaultModel::new(Response::new(
-                            Some(JoinResponse {
-                                game_id: join_game.game_id,
-                                is_host: true,
-                                success: true,
-                            }),
-                            ResponseOpCode::Join,
-                        )))
-                        .await
-                        .map_err(|_| ClientError::SendError)?;
-                } else {
-                    // Should join an already existing game through the shard communication protocol (redis)
-                    // Or by doing it locally, if the game is hosted on the same server as the socket client
-                    let redis_game: RedisGame = serde_json::from_str(&game)
-                        .map_err(|_| ClientError::InternalServerError("Failed to parse game"))?;
-
-                    // Check if game is on the same server
-                    if redis_game.shard_id == shard_id {
-                        // Todo: this is where the issue is, sockets got fetching two times for mutable
-                        let mut response = None;
-                        let game_host_send_channel;
-
-                        let host_nickname;
                         {
-                            let host = sockets.get_mut(&redis_game.host_id);
-                            match host {
-                                Some(mut game_host_client) => {
-                                    host_nickname =
-                                        game_host_client.nickname.as_ref().unwrap().clone();
-                                    game_host_send_channel =
-                                        Some(game_host_client.send_channel.clone());
-                                    if let Some(game_host_client_game) = &mut game_host_client.game
-                                    {
-                                        // Register the local client in the host game
-                                        if game_host_client_game
-                                            .register(PartialClient::new(
-                                                client_id,
-                                                nickname.to_owned(),
-                                                shard_id.to_string(),
-                                                true,
-                                                Some(client_write_channel),
-                                            ))
-                                            .await
-                                            .is_ok()
-                                        {
-                                            response = Some(DefaultModel::new(Response::new(
-                                                Some(JoinResponse {
-                                                    game_id: join_game.game_id.clone(),
-                                                    is_host: false,
-                                                    success: true,
-                                                }),
-                                                ResponseOpCode::Join,
-                                            )));
-                                        }
-                                    } else {
-                                        let _: redis::RedisResult<()> =
-                                            conn.del(format!("GAME:{}", join_game.game_id));
-                                        return Err(ClientError::InternalServerError(
-                                            "Host was not in the game, could not join it.",
-                                        ));
-                                    }
-                                }
-                                None => {
-                                    // Unregister the game if the host is gone
-                                    let _: redis::RedisResult<()> =
-                                        conn.del(format!("GAME:{}", join_game.game_id));
-                                    return Err(ClientError::ClientDoesNotExist(
-                                        "Socket client does not exist",
-                                    ));
-                                }
-                            }
-                        }
-
-                        // Continue here
-                        let mut client = sockets.get_mut(&client_id).unwrap();
-                        if response.is_some() {
-                            // Register the game for the client
+                            let mut client = sockets.get_mut(&client_id).unwrap();
                             client.game = Some(Game::new(
-                                false,
+                                true,
                                 join_game.game_id.clone(),
                                 PartialClient::new(
                                     client.id,
-                                    nickname.to_owned(),
-                                    shard_id.to_string(),
+                                    client.nickname.as_ref().unwrap().to_owned(),
+                                    redis_game.shard_id.clone(),
                                     true,
                                     Some(client.send_channel.clone()),
-                                ),
+                                    client.protocol,
+                                )
+                                .with_locale(client.locale.clone()),
                                 PartialClient::new(
-                                    redis_game.host_id,
-                                    host_nickname,
-                                    shard_id.to_string(),
+                                    client.id,
+                                    client.nickname.as_ref().unwrap().to_owned(),
+                                    redis_game.shard_id,
                                     true,
-                                    Some(game_host_send_channel.unwrap()),
-                                ),
-                                redis_pool,
+                                    Some(client.send_channel.clone()),
+                                    client.protocol,
+                                )
+                                .with_locale(client.locale.clone()),
+                                store,
                                 sockets.clone(),
+                                settings.share_verdict_cache,
                             ));
-                        } else {
-                            response = Some(DefaultModel::new(Response::new(
-                                Some(JoinResponse {
-                                    game_id: join_game.game_id,
-                                    is_host: false,
-                                    success: false,
-                                }),
-                                ResponseOpCode::Join,
-                            )));
                         }
 
+                        let client = sockets.get(&client_id).unwrap();
                         client
-                            .send_model(response.unwrap())
+                            .send_model(
+                                DefaultModel::new(Response::new(
+                                    Some(JoinResponse {
+                                        game_id: join_game.game_id,
+                                        is_host: true,
+                                        success: true,
+                                        nickname: Some(nickname),
+                                        player_id: Some(0),
+                                    }),
+                                    ResponseOpCode::Join,
+                                ))
+                                .with_nonce(nonce.as_deref()),
+                            )
                             .await
                             .map_err(|_| ClientError::SendError)?;
-                    } else {
-                        let mut conn = redis_pool.get().unwrap();
-                        let _: () = conn
-                            .del(join_game.game_id.clone())
-                            .map_err(|_| ClientError::InternalServerError("Game was corrupt"))?;
-                        let client = sockets.get(&client_id).unwrap();
-                        client
-                            .send_model(DefaultModel::new(Response::new(
-                                Some(JoinResponse {
+                    }
+                    StoredGame::Hosted(redis_game) => {
+                        // Should join an already existing game through the shard communication protocol (redis)
+                        // Or by doing it locally, if the game is hosted on the same server as the socket client
+
+                        // Check if game is on the same server
+                        if redis_game.shard_id == shard_id {
+                            // Register the client while holding the host socket, everyone is told
+                            // about the client only after the host socket was released
+                            let host =
+                                sockets.get_mut(&redis_game.host_id).map(|mut host_client| {
+                                    let game_host = PartialClient::new(
+                                        redis_game.host_id,
+                                        host_client.nickname.as_ref().unwrap().clone(),
+                                        shard_id.to_string(),
+                                        true,
+                                        Some(host_client.send_channel.clone()),
+                                        host_client.protocol,
+                                    )
+                                    .with_locale(host_client.locale.clone());
+
+                                    let registration = host_client.game.as_mut().map(|game| {
+                                        game.register(
+                                            PartialClient::new(
+                                                client_id,
+                                                nickname.to_owned(),
+                                                shard_id.to_string(),
+                                                true,
+                                                Some(client_write_channel),
+                                                client_protocol,
+                                            )
+                                            .with_locale(client_locale),
+                                        )
+                                        .map(|registered| (registered, game.participants()))
+                                    });
+
+                                    (game_host, registration)
+                                });
+
+                            let (game_host, registration) = match host {
+                                Some((game_host, Some(registration))) => (game_host, registration),
+                                Some((_, None)) => {
+                                    let _ = store.delete_game(&join_game.game_id).await;
+                                    return Err(ClientError::InternalServerError(
+                                        "Host was not in the game, could not join it.",
+                                    ));
+                                }
+                                None => {
+                                    // Unregister the game if the host is gone
+                                    let _ = store.delete_game(&join_game.game_id).await;
+                                    return Err(ClientError::ClientDoesNotExist(
+                                        "Socket client does not exist",
+                                    ));
+                                }
+                            };
+
+                            let response = match registration {
+                                Ok((registered, participants)) => {
+                                    Game::announce_registration(
+                                        &join_game.game_id,
+                                        &registered,
+                                        &participants,
+                                        &store,
+                                    )
+                                    .await;
+
+                                    // Register the game for the client
+                                    sockets.get_mut(&client_id).unwrap().game = Some(Game::new(
+                                        false,
+                                        join_game.game_id.clone(),
+                                        registered.clone(),
+                                        game_host,
+                                        store,
+                                        sockets.clone(),
+                                        settings.share_verdict_cache,
+                                    ));
+
+                                    JoinResponse {
+                                        game_id: join_game.game_id,
+                                        is_host: false,
+                                        success: true,
+                                        nickname: Some(registered.nickname),
+                                        player_id: Some(registered.player_id),
+                                    }
+                                }
+                                Err(_) => JoinResponse {
                                     game_id: join_game.game_id,
                                     is_host: false,
                                     success: false,
-                                }),
-                                ResponseOpCode::Join,
-                            )))
-                            .await
-                            .map_err(|_| ClientError::SendError)?;
-                        return Err(ClientError::NoGameWasFound);
+                                    nickname: None,
+                                    player_id: None,
+                                },
+                            };
+
+                            let client = sockets.get(&client_id).unwrap();
+                            client
+                                .send_model(
+                                    DefaultModel::new(Response::new(
+                                        Some(response),
+                                        ResponseOpCode::Join,
+                                    ))
+                                    .with_nonce(nonce.as_deref()),
+                                )
+                                .await
+                                .map_err(|_| ClientError::SendError)?;
+                        } else {
+                            // Games hosted on other shards can not be joined yet
+                            let client = sockets.get(&client_id).unwrap();
+                            client
+                                .send_model(
+                                    DefaultModel::new(Response::new(
+                                        Some(JoinResponse {
+                                            game_id: join_game.game_id,
+                                            is_host: false,
+                                            success: false,
+                                            nickname: None,
+                                            player_id: None,
+                                        }),
+                                        ResponseOpCode::Join,
+                                    ))
+                                    .with_nonce(nonce.as_deref()),
+                                )
+                                .await
+                                .map_err(|_| ClientError::SendError)?;
+                            return Err(ClientError::NoGameWasFound);
+                        }
                     }
                 }
             }
@@ -295,61 +313,51 @@ impl Request {
 
                 trace!("Received request to leave a game");
                 if client.game.is_none() {
-                    let _ = client
-                        .send_error(ClientError::NotInGame("Client was not in a game"))
-                        .await;
                     return Err(ClientError::NotInGame("Client was not in a game"));
                 }
 
                 // The client must be in a game at this point, it'sockets safe to unwrap the value
                 let game = client.game.take().unwrap();
+                drop(client);
 
-                // Dropping the game object will leave the game cleanly, or shut it down if the client was host
-                drop(game);
+                // Closing the game object will leave the game cleanly, or shut it down if the client was host
+                game.close().await;
             }
             RequestOpCode::Start => {
                 let mut client = sockets.get_mut(&client_id).unwrap();
-                if client.game.is_none() {
-                    let _ = client
-                        .send_error(ClientError::NotInGame("Client was not in a game"))
-                        .await;
-                    return Err(ClientError::NotInGame("Client was not in a game"));
-                } else if !client.game.as_ref().unwrap().is_host {
-                    // Dropping the game object will leave the game cleanly
-                    let game = client.game.take().unwrap();
-                    drop(game);
-
-                    let _ = client
-                        .send_error(ClientError::NotGameHost("Client was not the game host"))
-                        .await;
-                    return Err(ClientError::NotGameHost("Client was not the game host"));
+                match &client.game {
+                    None => return Err(ClientError::NotInGame("Client was not in a game")),
+                    Some(game) if !game.is_host => {
+                        return Err(ClientError::NotGameHost("Client was not the game host"))
+                    }
+                    _ => {}
                 }
 
                 // Parse the request
-                let request: StartRequest = serde_json::from_value(self.d.unwrap())
-                    .map_err(|_| ClientError::ParsingError)?;
+                let request: StartRequest = self.data()?;
 
                 // Start the game
                 client
                     .game
                     .as_mut()
                     .unwrap()
-                    .start(available_tasks, request.task_count)
+                    .start(available_tasks, request.task_count, request.disable_chat)
                     .await
-                    .map_err(|_| ClientError::SendError)?;
+                    .map_err(|e| {
+                        game_error(
+                            e,
+                            ClientError::InternalServerError("Game could not be started"),
+                        )
+                    })?;
             }
             RequestOpCode::Task => {
                 let client = sockets.get(&client_id).unwrap();
                 if client.game.is_none() {
-                    let _ = client
-                        .send_error(ClientError::NotInGame("Client was not in a game"))
-                        .await;
                     return Err(ClientError::NotInGame("Client was not in a game"));
                 }
 
                 // Parse the request
-                let request: TaskRequest = serde_json::from_value(self.d.unwrap())
-                    .map_err(|_| ClientError::ParsingError)?;
+                let request: TaskRequest = self.data()?;
 
                 let host_id = client.game.as_ref().unwrap().partial_host.id;
                 if let Some(host) = sockets.get(&host_id) {
@@ -357,42 +365,36 @@ impl Request {
                         match game.get_task_indexed(request.task_index) {
                             Ok(task) => {
                                 client
-                                    .send_model(DefaultModel::new(Response::new(
-                                        Some(TaskResponse {
-                                            task: task.to_owned(),
-                                        }),
-                                        ResponseOpCode::Task,
-                                    )))
+                                    .send_model(
+                                        DefaultModel::new(Response::new(
+                                            Some(TaskResponse {
+                                                task: PublicTaskView::localized(
+                                                    task,
+                                                    client.locale.as_deref(),
+                                                ),
+                                            }),
+                                            ResponseOpCode::Task,
+                                        ))
+                                        .with_nonce(nonce.as_deref()),
+                                    )
                                     .await
                                     .map_err(|_| ClientError::SendError)?;
                             }
                             Err(e) => match e {
                                 Some(_) => {
-                                    let error = ClientError::OutOfRangeTask;
-                                    let _ = client.send_error(error.clone()).await;
-                                    return Err(error);
+                                    return Err(ClientError::OutOfRangeTask);
                                 }
                                 None => {
-                                    let error = ClientError::GameNotStarted;
-                                    let _ = client.send_error(error.clone()).await;
-                                    return Err(error);
+                                    return Err(ClientError::GameNotStarted);
                                 }
                             },
                         }
                     } else {
-                        let _ = client
-                            .send_error(ClientError::InternalServerError(
-                                "Host was not in the same game",
-                            ))
-                            .await;
                         return Err(ClientError::InternalServerError(
                             "Host was not in the same game",
                         ));
                     }
                 } else {
-                    let _ = client
-                        .send_error(ClientError::InternalServerError("Host does not exist"))
-                        .await;
                     return Err(ClientError::InternalServerError("Host does not exist"));
                 }
             }
@@ -402,9 +404,6 @@ impl Request {
                 let host_id = {
                     let client = sockets.get(&client_id).unwrap();
                     if client.game.is_none() {
-                        let _ = client
-                            .send_error(ClientError::NotInGame("Client was not in a game"))
-                            .await;
                         return Err(ClientError::NotInGame("Client was not in a game"));
                     }
 
@@ -412,8 +411,7 @@ impl Request {
                 };
 
                 // Parse the request
-                let request: CompileRequest = serde_json::from_value(self.d.unwrap())
-                    .map_err(|_| ClientError::ParsingError)?;
+                let request: CompileRequest = self.data()?;
 
                 let response: (Option<_>, Option<ClientError>) = {
                     if let Some(host) = &mut sockets.get_mut(&host_id) {
@@ -421,11 +419,22 @@ impl Request {
                             if !game.is_started {
                                 (None, Some(ClientError::GameNotStarted))
                             } else {
-                                match game.prepare_code_test(request.task_index).await {
+                                match game
+                                    .prepare_code_test(
+                                        &client_id,
+                                        request.task_index,
+                                        request.language,
+                                        &request.code,
+                                    )
+                                    .await
+                                {
                                     Ok(r) => (Some(r), None),
-                                    Err(_) => (
+                                    Err(e) => (
                                         None,
-                                        Some(ClientError::CompilationError("Host was not host")),
+                                        Some(game_error(
+                                            e,
+                                            ClientError::CompilationError("Host was not host"),
+                                        )),
                                     ),
                                 }
                             }
@@ -445,24 +454,52 @@ impl Request {
 
                 // Check for error
                 if let Some(error) = response.1 {
-                    let client = sockets.get(&client_id).unwrap();
-                    let _ = client.send_error(error.clone()).await;
                     return Err(error);
                 }
 
+                let sandbox_input = match response.0.unwrap() {
+                    PreparedSubmission::Run(sandbox_input) => sandbox_input,
+                    PreparedSubmission::Cached(response) => {
+                        trace!("Reused the verdict of an identical submission");
+                        let client = sockets.get(&client_id).unwrap();
+                        client
+                            .send_model(
+                                DefaultModel::new(Response::new(
+                                    Some(response),
+                                    ResponseOpCode::Compile,
+                                ))
+                                .with_nonce(nonce.as_deref()),
+                            )
+                            .await
+                            .map_err(|_| ClientError::SendError)?;
+                        return Ok(());
+                    }
+                };
+
                 let response = match Game::run_code_test(
                     &client_id,
                     request.code.clone(),
-                    response.0.unwrap(),
+                    request.language,
+                    sandbox_input,
                 )
                 .await
+                .ok()
                 {
-                    Ok(r) => r,
-                    Err(_) => {
-                        let err = ClientError::CompilationError("failed to compile");
-                        // let client = sockets.get(&client_id).unwrap();
-                        // let _ = client.send_error(err.clone()).await;
-                        return Err(err);
+                    Some(r) => r,
+                    None => {
+                        // The sandbox could not be reached, the submission could not be judged
+                        let client = sockets.get(&client_id).unwrap();
+                        client
+                            .send_model(
+                                DefaultModel::new(Response::new(
+                                    Some(CompilationResponse::internal_error(request.task_index)),
+                                    ResponseOpCode::Compile,
+                                ))
+                                .with_nonce(nonce.as_deref()),
+                            )
+                            .await
+                            .map_err(|_| ClientError::SendError)?;
+                        return Ok(());
                     }
                 };
 
@@ -477,15 +514,19 @@ impl Request {
                                     .validate_code_test(
                                         &client_id,
                                         request.task_index,
-                                        request.code,
+                                        request.language,
+                                        &request.code,
                                         response,
                                     )
                                     .await
                                 {
                                     Ok(r) => (Some(r), None),
-                                    Err(_) => (
+                                    Err(e) => (
                                         None,
-                                        Some(ClientError::CompilationError("Compilation failed")),
+                                        Some(game_error(
+                                            e,
+                                            ClientError::CompilationError("Compilation failed"),
+                                        )),
                                     ),
                                 }
                             }
@@ -504,17 +545,18 @@ impl Request {
                 };
 
                 if let Some(error) = response.1 {
-                    let client = sockets.get(&client_id).unwrap();
-                    let _ = client.send_error(error.clone()).await;
                     return Err(error);
                 } else if let Some(response) = response.0 {
                     trace!("Finished compilation successfully");
                     let client = sockets.get(&client_id).unwrap();
                     client
-                        .send_model(DefaultModel::new(Response::new(
-                            Some(response),
-                            ResponseOpCode::Compile,
-                        )))
+                        .send_model(
+                            DefaultModel::new(Response::new(
+                                Some(response),
+                                ResponseOpCode::Compile,
+                            ))
+                            .with_nonce(nonce.as_deref()),
+                        )
                         .await
                         .map_err(|_| ClientError::SendError)?;
                 }
@@ -522,100 +564,236 @@ impl Request {
             RequestOpCode::Ping => {
                 let client = sockets.get(&client_id).unwrap();
                 client
-                    .send_model(DefaultModel::new(Response::new(
-                        Some(PingResponse {}),
-                        ResponseOpCode::Ping,
-                    )))
+                    .send_model(
+                        DefaultModel::new(Response::new(
+                            Some(PingResponse {}),
+                            ResponseOpCode::Ping,
+                        ))
+                        .with_nonce(nonce.as_deref()),
+                    )
                     .await
                     .map_err(|_| ClientError::SendError)?;
             }
             RequestOpCode::Identify => {
+                let request: IdentifyRequest = self.data()?;
+
                 let mut client = sockets.get_mut(&client_id).unwrap();
-                if client.nickname.is_none() {
-                    let request: IdentifyRequest = serde_json::from_value(self.d.unwrap())
-                        .map_err(|_| ClientError::ParsingError)?;
 
-                    // Todo: check how appropiate a nickname is
-                    client.nickname = Some(request.nickname);
-                    client
-                        .send_model(DefaultModel::new(Response::new(
-                            Some(IdentifyResponse { success: true }),
-                            ResponseOpCode::Identify,
-                        )))
-                        .await
-                        .map_err(|_| ClientError::SendError)?;
-                } else {
-                    client
-                        .send_model(DefaultModel::new(Response::new(
-                            Some(IdentifyResponse { success: false }),
-                            ResponseOpCode::Identify,
-                        )))
-                        .await
-                        .map_err(|_| ClientError::SendError)?;
+                // The locale is kept even if the nickname is rejected and can be changed later on,
+                // it applies to the statements of tasks requested afterwards
+                if request.locale.is_some() {
+                    client.locale = request.locale;
                 }
+
+                let response = if client.nickname.is_none() {
+                    match settings.nickname_filter.validate(&request.nickname) {
+                        Ok(nickname) => {
+                            client.nickname = Some(nickname.clone());
+                            IdentifyResponse {
+                                success: true,
+                                nickname: Some(nickname),
+                                reason: None,
+                            }
+                        }
+                        Err(reason) => IdentifyResponse {
+                            success: false,
+                            nickname: None,
+                            reason: Some(reason),
+                        },
+                    }
+                } else {
+                    IdentifyResponse {
+                        success: false,
+                        nickname: None,
+                        reason: Some(NicknameRejection::AlreadyIdentified),
+                    }
+                };
+
+                client
+                    .send_model(
+                        DefaultModel::new(Response::new(Some(response), ResponseOpCode::Identify))
+                            .with_nonce(nonce.as_deref()),
+                    )
+                    .await
+                    .map_err(|_| ClientError::SendError)?;
             }
             RequestOpCode::Create => {
-                let _: CreateRequest = serde_json::from_value(self.d.unwrap())
-                    .map_err(|_| ClientError::ParsingError)?;
+                let _: CreateRequest = self.data()?;
 
                 let alphabet: &[char] = &['1', '2', '3', '4', '5', '6', '7', '8', '9', '0'];
                 let game_id = nanoid::nanoid!(10, alphabet);
 
-                let mut conn = redis_pool
-                    .get()
-                    .map_err(|_| ClientError::InternalServerError("Cache error"))?;
-                let _: () = conn
-                    .set(format!("GAME:{}", game_id.clone()), "")
+                store
+                    .create_game(&game_id)
+                    .await
                     .map_err(|_| ClientError::InternalServerError("Cache error"))?;
 
-                let client = sockets.get(&client_id).unwrap();
+                let mut client = sockets.get_mut(&client_id).unwrap();
+                client.created_games.push(game_id.clone());
                 client
-                    .send_model(DefaultModel::new(Response::new(
-                        Some(CreateResponse { game_id }),
-                        ResponseOpCode::Create,
-                    )))
+                    .send_model(
+                        DefaultModel::new(Response::new(
+                            Some(CreateResponse { game_id }),
+                            ResponseOpCode::Create,
+                        ))
+                        .with_nonce(nonce.as_deref()),
+                    )
                     .await
                     .map_err(|_| ClientError::SendError)?;
             }
             RequestOpCode::Exists => {
-                let request: ExistsRequest = serde_json::from_value(self.d.unwrap())
-                    .map_err(|_| ClientError::ParsingError)?;
+                let request: ExistsRequest = self.data()?;
 
-                let mut conn = redis_pool
-                    .get()
-                    .map_err(|_| ClientError::InternalServerError("Cache error"))?;
+                // check if game exists in the state store
+                let res = match store.lookup_game(&request.game_id).await {
+                    Ok(Some(StoredGame::Created)) => ExistsResponse { exists: true },
+                    Ok(Some(StoredGame::Hosted(redis_game))) => {
+                        // Remove the game if the host has left but the service failed to remove the game
+                        if sockets.get(&redis_game.host_id).is_none() {
+                            let _ = store.delete_game(&request.game_id).await;
 
-                // check if game exists in redis
-                let res = match conn.get::<String, String>(format!("GAME:{}", request.game_id)) {
-                    Ok(game) => match game.as_str() {
-                        "" => ExistsResponse { exists: true },
-                        _ => {
-                            // Verify that game is still valid
-                            let redis_game: RedisGame =
-                                serde_json::from_str(&game).map_err(|_| {
-                                    ClientError::InternalServerError("Failed to parse game")
-                                })?;
-
-                            // Remove the game if the host has left but the service failed to remove the game
-                            if sockets.get(&redis_game.host_id).is_none() {
-                                let _: redis::RedisResult<()> =
-                                    conn.del(format!("GAME:{}", request.game_id));
-
-                                ExistsResponse { exists: false }
-                            } else {
-                                ExistsResponse { exists: true }
-                            }
+                            ExistsResponse { exists: false }
+                        } else {
+                            ExistsResponse { exists: true }
                         }
-                    },
+                    }
+                    Ok(None) => ExistsResponse { exists: false },
+                    Err(StateError::Corrupt(_)) => {
+                        return Err(ClientError::InternalServerError("Failed to parse game"));
+                    }
                     Err(_) => ExistsResponse { exists: false },
                 };
 
                 let client = sockets.get(&client_id).unwrap();
                 client
-                    .send_model(DefaultModel::new(Response::new(
-                        Some(res),
-                        ResponseOpCode::Exists,
-                    )))
+                    .send_model(
+                        DefaultModel::new(Response::new(Some(res), ResponseOpCode::Exists))
+                            .with_nonce(nonce.as_deref()),
+                    )
+                    .await
+                    .map_err(|_| ClientError::SendError)?;
+            }
+            RequestOpCode::Run => {
+                trace!("Received run request");
+                {
+                    let client = sockets.get(&client_id).unwrap();
+                    if client.game.is_none() {
+                        return Err(ClientError::NotInGame("Client was not in a game"));
+                    }
+                }
+
+                // Parse the request
+                let request: RunRequest = self.data()?;
+
+                // Custom runs are not submissions, the host game is never touched
+                let response = match Game::run_custom_input(
+                    &client_id,
+                    request.code,
+                    request.language,
+                    request.stdin,
+                )
+                .await
+                {
+                    Ok(result) => RunResponse::from_sandbox(&result),
+                    Err(_) => RunResponse::internal_error(),
+                };
+
+                let client = sockets.get(&client_id).unwrap();
+                client
+                    .send_model(
+                        DefaultModel::new(Response::new(Some(response), ResponseOpCode::Run))
+                            .with_nonce(nonce.as_deref()),
+                    )
+                    .await
+                    .map_err(|_| ClientError::SendError)?;
+            }
+            RequestOpCode::Chat => {
+                // Check if client is in game and if so, return game host id
+                let host_id = {
+                    let client = sockets.get(&client_id).unwrap();
+                    if client.game.is_none() {
+                        return Err(ClientError::NotInGame("Client was not in a game"));
+                    }
+
+                    client.game.as_ref().unwrap().partial_host.id
+                };
+
+                // Parse the request
+                let request: ChatRequest = self.data()?;
+
+                // Build the message while holding the host, then release it before sending
+                let (event, participants) = {
+                    let mut host = sockets
+                        .get_mut(&host_id)
+                        .ok_or(ClientError::InternalServerError("Host does not exist"))?;
+                    let game = host
+                        .game
+                        .as_mut()
+                        .ok_or(ClientError::InternalServerError("Host was not in the game"))?;
+
+                    (
+                        game.chat_message(&client_id, request.message)?,
+                        game.participants(),
+                    )
+                };
+
+                // Acknowledge the message to the sender before broadcasting it
+                sockets
+                    .get(&client_id)
+                    .unwrap()
+                    .send_model(
+                        DefaultModel::new(Response::new(
+                            Some(ChatResponse { success: true }),
+                            ResponseOpCode::Chat,
+                        ))
+                        .with_nonce(nonce.as_deref()),
+                    )
+                    .await
+                    .map_err(|_| ClientError::SendError)?;
+
+                let message = DefaultModel::new(GameEvent::new(event));
+                for participant in participants {
+                    if let Err(e) = participant
+                        .send_message(message.clone(), Priority::Normal, &store)
+                        .await
+                    {
+                        error!(
+                            "Failed to send chat message to client with id {}, error {}",
+                            participant.id, e
+                        );
+                    }
+                }
+            }
+            RequestOpCode::Mute => {
+                let mut client = sockets.get_mut(&client_id).unwrap();
+                match &client.game {
+                    None => return Err(ClientError::NotInGame("Client was not in a game")),
+                    Some(game) if !game.is_host => {
+                        return Err(ClientError::NotGameHost("Client was not the game host"))
+                    }
+                    _ => {}
+                }
+
+                // Parse the request
+                let request: MuteRequest = self.data()?;
+
+                client
+                    .game
+                    .as_mut()
+                    .unwrap()
+                    .set_muted(request.player_id, request.muted)?;
+
+                client
+                    .send_model(
+                        DefaultModel::new(Response::new(
+                            Some(MuteResponse {
+                                player_id: request.player_id,
+                                muted: request.muted,
+                            }),
+                            ResponseOpCode::Mute,
+                        ))
+                        .with_nonce(nonce.as_deref()),
+                    )
                     .await
                     .map_err(|_| ClientError::SendError)?;
             }
@@ -625,7 +803,40 @@ impl Request {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+impl Request {
+    /// Parses the data sent with the request, missing data is a parsing error
+    /// so the client is answered instead of disconnected
+    fn data<T: DeserializeOwned>(&self) -> Result<T, ClientError<'static>> {
+        let d = self.d.clone().ok_or(ClientError::ParsingError)?;
+        serde_json::from_value(d).map_err(|_| ClientError::ParsingError)
+    }
+}
+
+/// Keeps the client error a game operation failed with, other errors are replaced by the fallback
+fn game_error(
+    error: Box<dyn std::error::Error>,
+    fallback: ClientError<'static>,
+) -> ClientError<'static> {
+    error
+        .downcast_ref::<ClientError<'static>>()
+        .cloned()
+        .unwrap_or(fallback)
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    EnumIter,
+)]
 pub enum RequestOpCode {
     Join,
     Leave,
@@ -636,6 +847,9 @@ pub enum RequestOpCode {
     Identify,
     Create,
     Exists,
+    Chat,
+    Mute,
+    Run,
 }
 
 impl OpCodeFetcher for Request {
@@ -644,3 +858,580 @@ impl OpCodeFetcher for Request {
         OpCode::Request
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+    use serde_json::json;
+
+    use crate::service::{
+        state_store::memory::MemoryStateStore,
+        task_loader,
+        websocket::{
+            client::{game::CHAT_RATE_LIMIT_MESSAGES, rate_limit::RateLimiter, SocketClient},
+            outbound::{OutboundQueue, OutboundReceiver},
+            protocol::Protocol,
+        },
+    };
+
+    use super::*;
+
+    const TASKS: &str = r#"
+        [[tasks]]
+        task_id = "248fa5e0-a3ac-4de0-a077-307c80d12126"
+        statement = { body = "Echo the input" }
+
+        [[tasks.public_test_cases]]
+        stdin = "a"
+        expected = "a"
+        id = 0
+
+        [[tasks.private_test_cases]]
+        stdin = "b"
+        expected = "b"
+        id = 0
+    "#;
+
+    const GAME_ID: &str = "1234567890";
+
+    /// Handles requests the way a shard does, without any sockets
+    struct Shard {
+        sockets: Sockets,
+        store: Store,
+        tasks: Arc<Vec<GameTask>>,
+        settings: Arc<Settings>,
+
+        /// Keeps the outbound queues of the clients open
+        messages: Vec<OutboundReceiver>,
+    }
+
+    impl Shard {
+        fn new() -> Shard {
+            Shard {
+                sockets: Arc::new(DashMap::new()),
+                store: Arc::new(MemoryStateStore::default()),
+                tasks: Arc::new(task_loader::load_tasks(TASKS)),
+                settings: Arc::new(Settings::default()),
+                messages: vec![],
+            }
+        }
+
+        fn connect(&mut self) -> Uuid {
+            let (queue, messages) = OutboundQueue::new();
+            let client = SocketClient::new(
+                "127.0.0.1:1234".parse().unwrap(),
+                queue,
+                Protocol::DEFAULT,
+                None,
+                RateLimiter::new(&self.settings.rate_limits),
+            );
+            let client_id = client.id;
+            self.sockets.insert(client_id, client);
+            self.messages.push(messages);
+            client_id
+        }
+
+        async fn send(
+            &self,
+            client_id: Uuid,
+            op: RequestOpCode,
+            d: Option<Value>,
+        ) -> Result<(), ClientError<'static>> {
+            let request: Request = serde_json::from_value(json!({ "op": op, "d": d })).unwrap();
+            request
+                .handle_message(
+                    client_id,
+                    &self.sockets,
+                    self.store.clone(),
+                    self.tasks.clone(),
+                    self.settings.clone(),
+                    "shard",
+                )
+                .await
+        }
+
+        async fn identified(&mut self, nickname: &str) -> Uuid {
+            let client_id = self.connect();
+            self.send(
+                client_id,
+                RequestOpCode::Identify,
+                Some(json!({ "nickname": nickname })),
+            )
+            .await
+            .unwrap();
+            client_id
+        }
+
+        /// Creates a game with a host and a player, returns the host and the player
+        async fn game(&mut self) -> (Uuid, Uuid) {
+            self.store.create_game(GAME_ID).await.unwrap();
+            let host_id = self.identified("host").await;
+            let player_id = self.identified("player").await;
+            for client_id in [host_id, player_id] {
+                self.send(
+                    client_id,
+                    RequestOpCode::Join,
+                    Some(json!({ "game_id": GAME_ID })),
+                )
+                .await
+                .unwrap();
+            }
+
+            (host_id, player_id)
+        }
+
+        async fn started_game(&mut self, disable_chat: bool) -> (Uuid, Uuid) {
+            let (host_id, player_id) = self.game().await;
+            self.send(
+                host_id,
+                RequestOpCode::Start,
+                Some(json!({ "task_count": 1, "disable_chat": disable_chat })),
+            )
+            .await
+            .unwrap();
+
+            (host_id, player_id)
+        }
+
+        fn in_game(&self, client_id: Uuid) -> bool {
+            self.sockets.get(&client_id).unwrap().game.is_some()
+        }
+    }
+
+    macro_rules! assert_error {
+        ($result:expr, $pattern:pat) => {
+            match $result {
+                Err(error) => assert!(matches!(error, $pattern), "unexpected error {}", error),
+                Ok(_) => panic!("request succeeded"),
+            }
+        };
+    }
+
+    #[tokio::test]
+    async fn parsing_errors_are_not_fatal() {
+        let mut shard = Shard::new();
+        let client_id = shard.connect();
+
+        let error = shard
+            .send(
+                client_id,
+                RequestOpCode::Identify,
+                Some(json!({ "name": 1 })),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ClientError::ParsingError));
+        assert!(!error.is_fatal());
+    }
+
+    /// Nicknames of the connected client events a client received
+    fn connected_nicknames(messages: &mut OutboundReceiver) -> Vec<String> {
+        let mut nicknames = vec![];
+        while let Some(message) = messages.try_recv() {
+            let message: Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+            if message["d"]["op"] == "ConnectedClient" {
+                nicknames.push(
+                    message["d"]["event"]["nickname"]
+                        .as_str()
+                        .unwrap()
+                        .to_owned(),
+                );
+            }
+        }
+        nicknames
+    }
+
+    #[tokio::test]
+    async fn joining_announces_the_client_to_the_game() {
+        let mut shard = Shard::new();
+        let (host_id, player_id) = shard.game().await;
+
+        assert_eq!(connected_nicknames(&mut shard.messages[0]), ["player"]);
+        assert_eq!(connected_nicknames(&mut shard.messages[1]), ["host"]);
+        assert!(shard.in_game(host_id) && shard.in_game(player_id));
+    }
+
+    #[tokio::test]
+    async fn join_errors() {
+        let mut shard = Shard::new();
+        let anonymous_id = shard.connect();
+        let client_id = shard.identified("client").await;
+        let join = |game_id: &str| Some(json!({ "game_id": game_id }));
+
+        let error = shard
+            .send(client_id, RequestOpCode::Join, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ClientError::ParsingError));
+        assert!(!error.is_fatal());
+        assert_error!(
+            shard
+                .send(client_id, RequestOpCode::Join, Some(json!({})))
+                .await,
+            ClientError::ParsingError
+        );
+        assert_error!(
+            shard
+                .send(anonymous_id, RequestOpCode::Join, join(GAME_ID))
+                .await,
+            ClientError::ClientNotIdentified
+        );
+        assert_error!(
+            shard
+                .send(client_id, RequestOpCode::Join, join(GAME_ID))
+                .await,
+            ClientError::NoGameWasFound
+        );
+
+        let (host_id, _) = shard.game().await;
+        assert_error!(
+            shard
+                .send(host_id, RequestOpCode::Join, join(GAME_ID))
+                .await,
+            ClientError::AlreadyInGame(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn leave_errors() {
+        let mut shard = Shard::new();
+        let client_id = shard.identified("client").await;
+
+        assert_error!(
+            shard.send(client_id, RequestOpCode::Leave, None).await,
+            ClientError::NotInGame(_)
+        );
+    }
+
+    #[tokio::test]
+    async fn start_errors() {
+        let mut shard = Shard::new();
+        let client_id = shard.identified("client").await;
+        let start = Some(json!({ "task_count": 1 }));
+
+        assert_error!(
+            shard
+                .send(client_id, RequestOpCode::Start, start.clone())
+                .await,
+            ClientError::NotInGame(_)
+        );
+
+        let (host_id, player_id) = shard.game().await;
+        assert_error!(
+            shard
+                .send(player_id, RequestOpCode::Start, start.clone())
+                .await,
+            ClientError::NotGameHost(_)
+        );
+        assert!(shard.in_game(player_id));
+        assert_eq!(
+            shard
+                .sockets
+                .get(&host_id)
+                .unwrap()
+                .game
+                .as_ref()
+                .unwrap()
+                .connected_clients
+                .as_ref()
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert_error!(
+            shard
+                .send(host_id, RequestOpCode::Start, Some(json!({})))
+                .await,
+            ClientError::ParsingError
+        );
+        assert_error!(
+            shard
+                .send(
+                    host_id,
+                    RequestOpCode::Start,
+                    Some(json!({ "task_count": 2 }))
+                )
+                .await,
+            ClientError::InternalServerError(_)
+        );
+
+        let mut shard = Shard::new();
+        let (host_id, _) = shard.started_game(false).await;
+        assert_error!(
+            shard.send(host_id, RequestOpCode::Start, start).await,
+            ClientError::GameAlreadyStarted
+        );
+    }
+
+    #[tokio::test]
+    async fn task_errors() {
+        let mut shard = Shard::new();
+        let client_id = shard.identified("client").await;
+        let task = |task_index: usize| Some(json!({ "task_index": task_index }));
+
+        assert_error!(
+            shard.send(client_id, RequestOpCode::Task, task(0)).await,
+            ClientError::NotInGame(_)
+        );
+
+        let (_, player_id) = shard.game().await;
+        assert_error!(
+            shard.send(player_id, RequestOpCode::Task, task(0)).await,
+            ClientError::GameNotStarted
+        );
+        assert_error!(
+            shard
+                .send(player_id, RequestOpCode::Task, Some(json!({})))
+                .await,
+            ClientError::ParsingError
+        );
+
+        let mut shard = Shard::new();
+        let (_, player_id) = shard.started_game(false).await;
+        assert!(shard
+            .send(player_id, RequestOpCode::Task, task(0))
+            .await
+            .is_ok());
+        assert_error!(
+            shard.send(player_id, RequestOpCode::Task, task(1)).await,
+            ClientError::OutOfRangeTask
+        );
+    }
+
+    #[tokio::test]
+    async fn compile_errors() {
+        let mut shard = Shard::new();
+        let client_id = shard.identified("client").await;
+        let compile = |task_index: usize| Some(json!({ "task_index": task_index, "code": "" }));
+
+        assert_error!(
+            shard
+                .send(client_id, RequestOpCode::Compile, compile(0))
+                .await,
+            ClientError::NotInGame(_)
+        );
+
+        let (_, player_id) = shard.game().await;
+        assert_error!(
+            shard
+                .send(player_id, RequestOpCode::Compile, compile(0))
+                .await,
+            ClientError::GameNotStarted
+        );
+        assert_error!(
+            shard
+                .send(
+                    player_id,
+                    RequestOpCode::Compile,
+                    Some(json!({ "code": "" }))
+                )
+                .await,
+            ClientError::ParsingError
+        );
+
+        let mut shard = Shard::new();
+        let (_, player_id) = shard.started_game(false).await;
+        assert_error!(
+            shard
+                .send(player_id, RequestOpCode::Compile, compile(1))
+                .await,
+            ClientError::OutOfRangeTask
+        );
+    }
+
+    #[tokio::test]
+    async fn identify_create_and_exists_errors() {
+        let mut shard = Shard::new();
+        let client_id = shard.connect();
+
+        for op in [RequestOpCode::Identify, RequestOpCode::Create] {
+            let error = shard.send(client_id, op, None).await.unwrap_err();
+            assert!(matches!(error, ClientError::ParsingError));
+            assert!(!error.is_fatal());
+        }
+        assert!(shard
+            .send(client_id, RequestOpCode::Create, Some(json!({})))
+            .await
+            .is_ok());
+        assert_error!(
+            shard
+                .send(client_id, RequestOpCode::Exists, Some(json!({})))
+                .await,
+            ClientError::ParsingError
+        );
+        assert!(shard
+            .send(
+                client_id,
+                RequestOpCode::Exists,
+                Some(json!({ "game_id": GAME_ID }))
+            )
+            .await
+            .is_ok());
+        assert!(shard
+            .send(client_id, RequestOpCode::Ping, None)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn run_errors() {
+        let mut shard = Shard::new();
+        let client_id = shard.identified("client").await;
+
+        assert_error!(
+            shard
+                .send(client_id, RequestOpCode::Run, Some(json!({ "code": "" })))
+                .await,
+            ClientError::NotInGame(_)
+        );
+
+        let (_, player_id) = shard.game().await;
+        assert_error!(
+            shard
+                .send(player_id, RequestOpCode::Run, Some(json!({})))
+                .await,
+            ClientError::ParsingError
+        );
+    }
+
+    #[tokio::test]
+    async fn chat_errors() {
+        let mut shard = Shard::new();
+        let client_id = shard.identified("client").await;
+        let chat = |message: &str| Some(json!({ "message": message }));
+
+        assert_error!(
+            shard.send(client_id, RequestOpCode::Chat, chat("hi")).await,
+            ClientError::NotInGame(_)
+        );
+
+        let (host_id, player_id) = shard.game().await;
+        assert!(shard
+            .send(player_id, RequestOpCode::Chat, chat("hi"))
+            .await
+            .is_ok());
+        assert_error!(
+            shard.send(player_id, RequestOpCode::Chat, chat("  ")).await,
+            ClientError::InvalidMessage(_)
+        );
+        assert_error!(
+            shard
+                .send(player_id, RequestOpCode::Chat, chat(&"a".repeat(257)))
+                .await,
+            ClientError::ChatMessageTooLong
+        );
+        assert_error!(
+            shard
+                .send(player_id, RequestOpCode::Chat, Some(json!({})))
+                .await,
+            ClientError::ParsingError
+        );
+
+        shard
+            .send(
+                host_id,
+                RequestOpCode::Mute,
+                Some(json!({ "player_id": 1, "muted": true })),
+            )
+            .await
+            .unwrap();
+        assert_error!(
+            shard.send(player_id, RequestOpCode::Chat, chat("hi")).await,
+            ClientError::ChatMuted
+        );
+
+        let mut shard = Shard::new();
+        let (host_id, player_id) = shard.started_game(true).await;
+        assert_error!(
+            shard.send(player_id, RequestOpCode::Chat, chat("hi")).await,
+            ClientError::ChatDisabled
+        );
+        assert!(shard
+            .send(host_id, RequestOpCode::Chat, chat("hi"))
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn chat_is_acknowledged_and_rate_limited() {
+        let mut shard = Shard::new();
+        let (_, player_id) = shard.game().await;
+        let chat: Request = serde_json::from_value(json!({
+            "op": RequestOpCode::Chat,
+            "d": { "message": "hi" },
+            "nonce": "chat-1",
+        }))
+        .unwrap();
+        chat.handle_message(
+            player_id,
+            &shard.sockets,
+            shard.store.clone(),
+            shard.tasks.clone(),
+            shard.settings.clone(),
+            "shard",
+        )
+        .await
+        .unwrap();
+
+        let player_messages = &mut shard.messages[1];
+        let mut acknowledged = false;
+        while let Some(message) = player_messages.try_recv() {
+            let message: Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+            if message["nonce"] == "chat-1" {
+                assert_eq!(message["d"]["d"]["success"], true);
+                acknowledged = true;
+            }
+        }
+        assert!(acknowledged, "chat message was not acknowledged");
+
+        for _ in 1..CHAT_RATE_LIMIT_MESSAGES {
+            shard
+                .send(
+                    player_id,
+                    RequestOpCode::Chat,
+                    Some(json!({ "message": "hi" })),
+                )
+                .await
+                .unwrap();
+        }
+        assert_error!(
+            shard
+                .send(
+                    player_id,
+                    RequestOpCode::Chat,
+                    Some(json!({ "message": "hi" }))
+                )
+                .await,
+            ClientError::ChatRateLimited
+        );
+    }
+
+    #[tokio::test]
+    async fn mute_errors() {
+        let mut shard = Shard::new();
+        let client_id = shard.identified("client").await;
+        let mute = |player_id: u32| Some(json!({ "player_id": player_id, "muted": true }));
+
+        assert_error!(
+            shard.send(client_id, RequestOpCode::Mute, mute(1)).await,
+            ClientError::NotInGame(_)
+        );
+
+        let (host_id, player_id) = shard.game().await;
+        assert_error!(
+            shard.send(player_id, RequestOpCode::Mute, mute(0)).await,
+            ClientError::NotGameHost(_)
+        );
+        assert_error!(
+            shard
+                .send(host_id, RequestOpCode::Mute, Some(json!({})))
+                .await,
+            ClientError::ParsingError
+        );
+        assert_error!(
+            shard.send(host_id, RequestOpCode::Mute, mute(7)).await,
+            ClientError::ClientDoesNotExist(_)
+        );
+    }
+}
@@ -1,7 +1,14 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::service::websocket::client::game::language::Language;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CompileRequest {
     pub(crate) code: String,
     pub(crate) task_index: usize,
+
+    /// Language the code is written in, part of the key identical submissions are cached by
+    #[serde(default)]
+    pub(crate) language: Language,
 }
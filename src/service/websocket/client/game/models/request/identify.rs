@@ -1,6 +1,11 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IdentifyRequest {
     pub(crate) nickname: String,
-}
\ No newline at end of file
+
+    /// Preferred locale for task statements, e.g. "sv-SE"
+    #[serde(default)]
+    pub(crate) locale: Option<String>,
+}
@@ -1,6 +1,7 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct JoinRequest {
     pub(crate) game_id: String,
 }
\ No newline at end of file
@@ -0,0 +1,11 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::service::websocket::client::game::PlayerId;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MuteRequest {
+    /// The player to mute or unmute
+    pub(crate) player_id: PlayerId,
+    pub(crate) muted: bool,
+}
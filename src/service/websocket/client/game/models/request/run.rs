@@ -0,0 +1,16 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::service::websocket::client::game::language::Language;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RunRequest {
+    pub(crate) code: String,
+
+    #[serde(default)]
+    pub(crate) language: Language,
+
+    /// Custom input given to the program
+    #[serde(default)]
+    pub(crate) stdin: String,
+}
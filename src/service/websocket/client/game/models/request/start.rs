@@ -1,6 +1,11 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StartRequest {
     pub(crate) task_count: usize,
+
+    /// Disables the in-game chat for everyone but the host once the game has started
+    #[serde(default)]
+    pub(crate) disable_chat: bool,
 }
@@ -1,4 +1,6 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
+use strum::EnumIter;
 
 use crate::service::websocket::client::models::{OpCodeFetcher, OpCode};
 
@@ -12,9 +14,12 @@ pub mod compile;
 pub mod identify;
 pub mod create;
 pub mod exists;
+pub mod mute;
+pub mod run;
+pub mod chat;
 
 // Models for responses
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Response<T> {
     d: Option<T>,
     op: ResponseOpCode,
@@ -26,7 +31,9 @@ impl<T> Response<T> {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema, EnumIter,
+)]
 pub enum ResponseOpCode {
     Join,
     Leave,
@@ -38,6 +45,9 @@ pub enum ResponseOpCode {
     Identify,
     Create,
     Exists,
+    Mute,
+    Run,
+    Chat,
 }
 
 impl<T> OpCodeFetcher for Response<T> {
@@ -0,0 +1,8 @@
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+/// Sent to the sender once its chat message was accepted and broadcast to the game
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChatResponse {
+    pub(crate) success: bool,
+}
@@ -1,15 +1,49 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 
-use self::progress::PublicTestProgress;
+use self::{progress::PublicTestProgress, verdict::Verdict};
 
 pub mod progress;
+pub mod verdict;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CompilationResponse {
     pub(crate) task_index: usize,
+
+    /// Verdict of the whole submission, decided by the first test that did not pass
+    pub(crate) verdict: Verdict,
     pub(crate) public_test_progress: Vec<PublicTestProgress>,
-    pub(crate) is_done: bool,
-    pub(crate) is_done_public_tests: bool,
-    pub(crate) is_done_private_tests: bool,
-    pub(crate) stderr: String,
-}
\ No newline at end of file
+
+    /// Index of the first failed private test, the test case itself is never revealed
+    pub(crate) failed_private_test: Option<usize>,
+
+    /// Output of the compiler, only defined if the verdict is CompileError
+    pub(crate) compiler_output: Option<String>,
+}
+
+impl CompilationResponse {
+    pub fn new(
+        task_index: usize,
+        verdict: Verdict,
+        public_test_progress: Vec<PublicTestProgress>,
+    ) -> Self {
+        CompilationResponse {
+            task_index,
+            verdict,
+            public_test_progress,
+            failed_private_test: None,
+            compiler_output: None,
+        }
+    }
+
+    pub fn compile_error(task_index: usize, compiler_output: String) -> Self {
+        CompilationResponse {
+            compiler_output: Some(compiler_output),
+            ..CompilationResponse::new(task_index, Verdict::CompileError, vec![])
+        }
+    }
+
+    pub fn internal_error(task_index: usize) -> Self {
+        CompilationResponse::new(task_index, Verdict::InternalError, vec![])
+    }
+}
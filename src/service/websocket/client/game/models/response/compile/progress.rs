@@ -1,33 +1,31 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use super::verdict::Verdict;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PublicTestProgress {
     pub(crate) test_index: usize,
-    pub(crate) succeeded: bool,
+    pub(crate) verdict: Verdict,
     pub(crate) stdout: String,
+    pub(crate) stderr: String,
     pub(crate) expected: String,
 }
 
 impl PublicTestProgress {
-    pub fn new(test_index: usize, stdout: String, expected: String) -> Self {
+    pub fn new(
+        test_index: usize,
+        verdict: Verdict,
+        stdout: String,
+        stderr: String,
+        expected: String,
+    ) -> Self {
         PublicTestProgress {
             test_index,
-            succeeded: true,
+            verdict,
             stdout,
-            expected
+            stderr,
+            expected,
         }
     }
-
-    pub fn new_failed(test_index: usize, stdout: String, expected: String) -> Self {
-        PublicTestProgress {
-            test_index,
-            succeeded: false,
-            stdout,
-            expected
-        }
-    }
-
-    pub fn make_not_failed(&mut self) {
-        self.succeeded = true;
-    }
 }
@@ -0,0 +1,75 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::service::websocket::client::game::sandbox::{ExecutionStatus, SandboxResponse};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Verdict {
+    CompileError,
+    WrongAnswer,
+    RuntimeError,
+    TimeLimit,
+    MemoryLimit,
+    Accepted,
+
+    /// The submission could not be judged, e.g. the sandbox was unreachable
+    InternalError,
+}
+
+impl Verdict {
+    /// Judges a single test of a sandbox run, the execution status takes priority over the output
+    ///
+    /// Returns the verdict together with the trimmed stdout of the test
+    pub fn from_sandbox(
+        result: &SandboxResponse,
+        index: usize,
+        expected: &str,
+    ) -> (Verdict, String) {
+        if !result.success {
+            return (Verdict::CompileError, String::new());
+        }
+
+        let stdout = result.stdout.get(index);
+        if let Some(verdict) = result
+            .statuses
+            .get(index)
+            .and_then(|status| Verdict::from_execution_status(*status))
+        {
+            return (verdict, stdout.cloned().unwrap_or_default());
+        }
+
+        // Every test that exited normally has to have an output
+        let mut stdout = match stdout {
+            Some(stdout) => stdout.to_owned(),
+            None => return (Verdict::InternalError, String::new()),
+        };
+        if stdout.ends_with('\n') {
+            stdout.pop();
+        }
+
+        if stdout == expected {
+            (Verdict::Accepted, stdout)
+        } else {
+            (Verdict::WrongAnswer, stdout)
+        }
+    }
+
+    /// If running the same submission again always results in this verdict
+    pub fn is_deterministic(&self) -> bool {
+        matches!(
+            self,
+            Verdict::Accepted | Verdict::WrongAnswer | Verdict::CompileError
+        )
+    }
+
+    /// Maps the execution status of a test reported by the sandbox,
+    /// returns None if the program exited normally and the output has to be compared
+    pub fn from_execution_status(status: i32) -> Option<Verdict> {
+        match ExecutionStatus::from_i32(status)? {
+            ExecutionStatus::Ok => None,
+            ExecutionStatus::RuntimeError => Some(Verdict::RuntimeError),
+            ExecutionStatus::TimeLimitExceeded => Some(Verdict::TimeLimit),
+            ExecutionStatus::MemoryLimitExceeded => Some(Verdict::MemoryLimit),
+        }
+    }
+}
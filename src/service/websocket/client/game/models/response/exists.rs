@@ -1,6 +1,7 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ExistsResponse {
     pub(crate) exists: bool,
 }
\ No newline at end of file
@@ -1,6 +1,15 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::service::websocket::client::nickname::NicknameRejection;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IdentifyResponse {
     pub(crate) success: bool,
-}
\ No newline at end of file
+
+    /// The normalized nickname, only defined if the nickname was accepted
+    pub(crate) nickname: Option<String>,
+
+    /// Why the nickname was rejected, only defined if it was not accepted
+    pub(crate) reason: Option<NicknameRejection>,
+}
@@ -1,8 +1,17 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::service::websocket::client::game::PlayerId;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct JoinResponse {
     pub(crate) game_id: String,
     pub(crate) is_host: bool,
     pub(crate) success: bool,
-}
\ No newline at end of file
+
+    /// The nickname used within the game, may have a suffix if the nickname was already taken
+    pub(crate) nickname: Option<String>,
+
+    /// The handle other players in the game see this client as
+    pub(crate) player_id: Option<PlayerId>,
+}
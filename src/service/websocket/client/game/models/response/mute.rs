@@ -0,0 +1,10 @@
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+
+use crate::service::websocket::client::game::PlayerId;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MuteResponse {
+    pub(crate) player_id: PlayerId,
+    pub(crate) muted: bool,
+}
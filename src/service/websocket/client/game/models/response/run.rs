@@ -0,0 +1,121 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::service::websocket::client::game::{
+    models::response::compile::verdict::Verdict, sandbox::SandboxResponse,
+};
+
+/// How a run with custom input ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum RunStatus {
+    /// The program exited normally
+    Ok,
+    CompileError,
+    RuntimeError,
+    TimeLimit,
+    MemoryLimit,
+
+    /// The program could not be run, e.g. the sandbox was unreachable
+    InternalError,
+}
+
+/// Result of running code with custom input, the output is never judged
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RunResponse {
+    pub(crate) status: RunStatus,
+
+    /// Exit code of the program, only defined if it exited by itself
+    pub(crate) exit_code: Option<i32>,
+    pub(crate) stdout: String,
+    pub(crate) stderr: String,
+}
+
+impl RunResponse {
+    pub fn from_sandbox(result: &SandboxResponse) -> Self {
+        if !result.success {
+            return RunResponse {
+                status: RunStatus::CompileError,
+                exit_code: None,
+                stdout: String::new(),
+                stderr: result.stderr.join(""),
+            };
+        }
+
+        let status = match result
+            .statuses
+            .first()
+            .and_then(|status| Verdict::from_execution_status(*status))
+        {
+            None => RunStatus::Ok,
+            Some(Verdict::TimeLimit) => RunStatus::TimeLimit,
+            Some(Verdict::MemoryLimit) => RunStatus::MemoryLimit,
+            Some(_) => RunStatus::RuntimeError,
+        };
+
+        let exit_code = match status {
+            RunStatus::Ok | RunStatus::RuntimeError => result.exit_codes.first().copied(),
+            _ => None,
+        };
+
+        RunResponse {
+            status,
+            exit_code,
+            stdout: result.stdout.first().cloned().unwrap_or_default(),
+            stderr: result.stderr.first().cloned().unwrap_or_default(),
+        }
+    }
+
+    pub fn internal_error() -> Self {
+        RunResponse {
+            status: RunStatus::InternalError,
+            exit_code: None,
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::service::websocket::client::game::sandbox::ExecutionStatus;
+
+    use super::*;
+
+    fn result(status: ExecutionStatus, exit_code: i32) -> SandboxResponse {
+        SandboxResponse {
+            success: true,
+            stdout: vec!["out".to_string()],
+            stderr: vec!["err".to_string()],
+            statuses: vec![status as i32],
+            exit_codes: vec![exit_code],
+        }
+    }
+
+    #[test]
+    fn reports_the_exit_code_of_finished_runs() {
+        let response = RunResponse::from_sandbox(&result(ExecutionStatus::Ok, 0));
+        assert_eq!(response.status, RunStatus::Ok);
+        assert_eq!(response.exit_code, Some(0));
+        assert_eq!(response.stdout, "out");
+
+        let response = RunResponse::from_sandbox(&result(ExecutionStatus::RuntimeError, 101));
+        assert_eq!(response.status, RunStatus::RuntimeError);
+        assert_eq!(response.exit_code, Some(101));
+    }
+
+    #[test]
+    fn killed_runs_have_no_exit_code() {
+        let response = RunResponse::from_sandbox(&result(ExecutionStatus::TimeLimitExceeded, 0));
+        assert_eq!(response.status, RunStatus::TimeLimit);
+        assert_eq!(response.exit_code, None);
+
+        let response = RunResponse::from_sandbox(&SandboxResponse {
+            success: false,
+            stderr: vec!["error[E0425]".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(response.status, RunStatus::CompileError);
+        assert_eq!(response.exit_code, None);
+        assert_eq!(response.stderr, "error[E0425]");
+    }
+}
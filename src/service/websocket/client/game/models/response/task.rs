@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 
-use crate::service::websocket::client::game::task::GameTask;
+use crate::service::websocket::client::game::task::view::PublicTaskView;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TaskResponse {
-    pub(crate) task: GameTask
+    pub(crate) task: PublicTaskView
 }
\ No newline at end of file
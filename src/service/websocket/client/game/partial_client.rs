@@ -1,18 +1,27 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
 
-use r2d2::Pool;
 use serde::{Deserialize, Serialize};
-use tokio_tungstenite::tungstenite::Message;
 use uuid::Uuid;
 
 use crate::service::{
-    redis_pool::RedisConnectionManager,
-    websocket::{client::models::DefaultModel, SocketSender},
+    state_store::Store,
+    websocket::{
+        client::{game::PlayerId, models::DefaultModel},
+        outbound::Priority,
+        protocol::Protocol,
+        SocketSender,
+    },
 };
 
 #[derive(Debug, Clone)]
 pub struct PartialClient {
     pub(crate) id: Uuid,
+
+    /// Handle that identifies the client to other players in the game, assigned by the host
+    pub(crate) player_id: PlayerId,
     pub(crate) nickname: String,
 
     /// The shard_id where the client is registered
@@ -25,8 +34,23 @@ pub struct PartialClient {
     /// Only available on local sockets, prevents deadlocking within games
     pub(crate) write_channel: Option<SocketSender>,
 
+    /// Protocol negotiated by the socket, messages are encoded with it
+    pub(crate) protocol: Protocol,
+
+    /// Locale the client identified with, used to translate task statements
+    pub(crate) locale: Option<String>,
+
     /// Only available if the client is a client in a game
     pub(crate) task_progress: Option<HashMap<usize, bool>>,
+
+    /// Amount of judged submissions per task index
+    pub(crate) submissions: HashMap<usize, usize>,
+
+    /// If the host has muted the client in the game chat
+    pub(crate) muted: bool,
+
+    /// When the most recent chat messages were sent, used for rate limiting
+    pub(crate) chat_history: VecDeque<Instant>,
 }
 
 impl PartialClient {
@@ -36,21 +60,34 @@ impl PartialClient {
         shard_id: String,
         is_local: bool,
         write_channel: Option<SocketSender>,
+        protocol: Protocol,
     ) -> PartialClient {
         PartialClient {
             id,
+            player_id: 0,
             nickname,
             shard_id,
             is_local,
             write_channel,
+            protocol,
+            locale: None,
             task_progress: None,
+            submissions: HashMap::new(),
+            muted: false,
+            chat_history: VecDeque::new(),
         }
     }
 
+    pub fn with_locale(mut self, locale: Option<String>) -> Self {
+        self.locale = locale;
+        self
+    }
+
     pub async fn send_message<'a, T>(
         &self,
         message: DefaultModel<T>,
-        _redis_pool: &Pool<RedisConnectionManager>,
+        priority: Priority,
+        _store: &Store,
     ) -> Result<(), Box<dyn std::error::Error>>
     where
         T: Serialize + Deserialize<'a>,
@@ -59,8 +96,7 @@ impl PartialClient {
             self.write_channel
                 .as_ref()
                 .unwrap()
-                .send(Message::Text(serde_json::to_string(&message).unwrap()))
-                .await?;
+                .push(self.protocol.encode(&message)?, priority)?;
         }
 
         Ok(())
@@ -1,21 +1,113 @@
+pub mod limits;
+pub mod statement;
 pub mod test_case;
+pub mod view;
 
-use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
-use self::test_case::TestCase;
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use self::{limits::TaskLimits, statement::TaskStatement, test_case::TestCase};
+
+use super::{
+    language::Language,
+    sandbox::{self, ResourceLimits},
+};
+
+/// Internal task definition, never serialized to clients as it contains the private test cases,
+/// see the `view` module for the models that are sent to clients
+#[derive(Debug, Clone, Deserialize)]
 pub struct GameTask {
     /// Global id that identifies the task among all existing ones in the database
     pub(crate) task_id: Uuid,
 
-    /// Could be replaced by some formatting thingy
-    pub(crate) question: String,
+    /// Statement shown when no localized statement matches the locale of the client
+    pub(crate) statement: TaskStatement,
+
+    /// Translated statements keyed by locale, e.g. "sv" or "en-GB"
+    #[serde(default)]
+    pub(crate) localized_statements: HashMap<String, TaskStatement>,
+
+    #[serde(default)]
+    pub(crate) limits: TaskLimits,
 
     /// Public test cases
     pub(crate) public_test_cases: Vec<TestCase>,
 
     /// Test cases, are validated with stdout
-    #[serde(skip_serializing)]
     pub(crate) private_test_cases: Vec<TestCase>,
 }
+
+impl GameTask {
+    /// Picks the statement for a locale, "sv-SE" falls back to "sv" and then to the default statement
+    pub fn statement(&self, locale: Option<&str>) -> &TaskStatement {
+        let locale = match locale {
+            Some(locale) => locale,
+            None => return &self.statement,
+        };
+
+        self.localized_statements
+            .get(locale)
+            .or_else(|| {
+                locale
+                    .split(['-', '_'])
+                    .next()
+                    .and_then(|language| self.localized_statements.get(language))
+            })
+            .unwrap_or(&self.statement)
+    }
+
+    /// Checks that the statements only use public test cases as samples,
+    /// private test cases must never be shown to the players
+    pub fn validate(&self) -> Result<(), String> {
+        for statement in std::iter::once(&self.statement).chain(self.localized_statements.values())
+        {
+            for id in &statement.sample_test_ids {
+                if !self.public_test_cases.iter().any(|test| test.id == *id) {
+                    return Err(format!("sample test {} is not a public test case", id));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Hash that identifies a submission to this task, identical code in the same language
+    /// always results in the same hash regardless of which game or shard it was submitted on
+    pub fn submission_hash(&self, language: Language, code: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.task_id.as_bytes());
+        hasher.update((sandbox::Language::from(language) as i32).to_be_bytes());
+        hasher.update(code.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Collects the inputs of every test case to run in a single sandbox pass, the public tests come first.
+    /// Expected outputs stay on the server, the submitted code runs in the sandbox and must not see them,
+    /// so the sandbox runs every input even when a public test fails
+    pub fn sandbox_input(&self) -> SandboxInput {
+        let mut input = SandboxInput::default();
+
+        for test in self
+            .public_test_cases
+            .iter()
+            .chain(self.private_test_cases.iter())
+        {
+            input.stdin.push(test.stdin.to_owned());
+            input.limits.push(ResourceLimits::from(
+                self.limits.with_overrides(test.limits.as_ref()),
+            ));
+        }
+
+        input
+    }
+}
+
+/// Test inputs of a single sandbox run, every vector has one entry per test
+#[derive(Debug, Clone, Default)]
+pub struct SandboxInput {
+    pub(crate) stdin: Vec<String>,
+    pub(crate) limits: Vec<ResourceLimits>,
+}
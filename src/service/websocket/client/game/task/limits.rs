@@ -0,0 +1,76 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::service::websocket::client::game::sandbox::ResourceLimits;
+
+/// Resource limits a submission has to stay within for every test case
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct TaskLimits {
+    #[serde(default = "default_cpu_time_limit_ms")]
+    pub(crate) cpu_time_limit_ms: u64,
+
+    /// Includes time spent waiting, e.g. sleeping or blocking on io
+    #[serde(default = "default_wall_time_limit_ms")]
+    pub(crate) wall_time_limit_ms: u64,
+
+    #[serde(default = "default_memory_limit_mb")]
+    pub(crate) memory_limit_mb: u64,
+}
+
+impl TaskLimits {
+    /// Returns the limits with the overrides of a single test case applied
+    pub fn with_overrides(&self, overrides: Option<&TestLimits>) -> TaskLimits {
+        match overrides {
+            Some(overrides) => TaskLimits {
+                cpu_time_limit_ms: overrides
+                    .cpu_time_limit_ms
+                    .unwrap_or(self.cpu_time_limit_ms),
+                wall_time_limit_ms: overrides
+                    .wall_time_limit_ms
+                    .unwrap_or(self.wall_time_limit_ms),
+                memory_limit_mb: overrides.memory_limit_mb.unwrap_or(self.memory_limit_mb),
+            },
+            None => *self,
+        }
+    }
+}
+
+impl Default for TaskLimits {
+    fn default() -> Self {
+        TaskLimits {
+            cpu_time_limit_ms: default_cpu_time_limit_ms(),
+            wall_time_limit_ms: default_wall_time_limit_ms(),
+            memory_limit_mb: default_memory_limit_mb(),
+        }
+    }
+}
+
+impl From<TaskLimits> for ResourceLimits {
+    fn from(limits: TaskLimits) -> Self {
+        ResourceLimits {
+            cpu_time_ms: limits.cpu_time_limit_ms,
+            wall_time_ms: limits.wall_time_limit_ms,
+            memory_kb: limits.memory_limit_mb * 1024,
+        }
+    }
+}
+
+/// Per test case overrides of the task limits
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct TestLimits {
+    pub(crate) cpu_time_limit_ms: Option<u64>,
+    pub(crate) wall_time_limit_ms: Option<u64>,
+    pub(crate) memory_limit_mb: Option<u64>,
+}
+
+fn default_cpu_time_limit_ms() -> u64 {
+    2000
+}
+
+fn default_wall_time_limit_ms() -> u64 {
+    5000
+}
+
+fn default_memory_limit_mb() -> u64 {
+    256
+}
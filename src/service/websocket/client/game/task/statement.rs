@@ -0,0 +1,23 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Problem statement of a task, every text field is formatted with markdown
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TaskStatement {
+    pub(crate) body: String,
+
+    /// Describes how the input is given to the program through stdin
+    #[serde(default)]
+    pub(crate) input_format: Option<String>,
+
+    /// Describes what the program is expected to write to stdout
+    #[serde(default)]
+    pub(crate) output_format: Option<String>,
+
+    #[serde(default)]
+    pub(crate) constraints: Vec<String>,
+
+    /// Ids of the public test cases that are shown as examples together with the statement
+    #[serde(default)]
+    pub(crate) sample_test_ids: Vec<usize>,
+}
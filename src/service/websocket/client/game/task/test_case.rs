@@ -1,6 +1,8 @@
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use super::limits::TestLimits;
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct TestCase {
     /// stdin that the testcase sends when it runs
     pub(crate) stdin: String,
@@ -10,4 +12,8 @@ pub struct TestCase {
 
     /// local id
     pub(crate) id: usize,
-}
\ No newline at end of file
+
+    /// Overrides the limits of the task for this test case only
+    #[serde(default)]
+    pub(crate) limits: Option<TestLimits>,
+}
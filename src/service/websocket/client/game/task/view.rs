@@ -0,0 +1,194 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{limits::TaskLimits, statement::TaskStatement, test_case::TestCase, GameTask};
+
+/// Task as seen by other players, only contains the statement
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TaskStatementView {
+    pub(crate) task_id: Uuid,
+    pub(crate) statement: TaskStatement,
+}
+
+impl TaskStatementView {
+    /// Creates the view with the statement translated to the locale if available
+    pub fn localized(task: &GameTask, locale: Option<&str>) -> Self {
+        TaskStatementView {
+            task_id: task.task_id,
+            statement: task.statement(locale).clone(),
+        }
+    }
+}
+
+impl From<&GameTask> for TaskStatementView {
+    fn from(task: &GameTask) -> Self {
+        TaskStatementView::localized(task, None)
+    }
+}
+
+/// Task as seen by a player solving it, contains the statement and the public test cases
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PublicTaskView {
+    pub(crate) task_id: Uuid,
+    pub(crate) statement: TaskStatement,
+    pub(crate) limits: TaskLimits,
+    pub(crate) public_test_cases: Vec<PublicTestCaseView>,
+}
+
+impl PublicTaskView {
+    /// Creates the view with the statement translated to the locale if available
+    pub fn localized(task: &GameTask, locale: Option<&str>) -> Self {
+        PublicTaskView {
+            task_id: task.task_id,
+            statement: task.statement(locale).clone(),
+            limits: task.limits,
+            public_test_cases: task
+                .public_test_cases
+                .iter()
+                .map(PublicTestCaseView::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&GameTask> for PublicTaskView {
+    fn from(task: &GameTask) -> Self {
+        PublicTaskView::localized(task, None)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PublicTestCaseView {
+    pub(crate) id: usize,
+    pub(crate) stdin: String,
+    pub(crate) expected: String,
+}
+
+impl From<&TestCase> for PublicTestCaseView {
+    fn from(test_case: &TestCase) -> Self {
+        PublicTestCaseView {
+            id: test_case.id,
+            stdin: test_case.stdin.clone(),
+            expected: test_case.expected.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Serialize;
+
+    use crate::service::websocket::client::{
+        game::{
+            models::{
+                event::{task::TaskGameEvent, task_finished::TaskFinishedGameEvent},
+                response::task::TaskResponse,
+                GameEvent, Response, ResponseOpCode,
+            },
+            sandbox::SandboxResponse,
+            task::limits::TaskLimits,
+            Game,
+        },
+        models::DefaultModel,
+    };
+
+    use super::*;
+
+    const PRIVATE_INPUT: &str = "private input marker";
+    const PRIVATE_OUTPUT: &str = "private output marker";
+
+    fn statement(body: &str) -> TaskStatement {
+        TaskStatement {
+            body: body.to_string(),
+            input_format: None,
+            output_format: None,
+            constraints: vec![],
+            sample_test_ids: vec![0],
+        }
+    }
+
+    fn test_case(id: usize, stdin: &str, expected: &str) -> TestCase {
+        TestCase {
+            stdin: stdin.to_string(),
+            expected: expected.to_string(),
+            id,
+            limits: None,
+        }
+    }
+
+    fn task() -> GameTask {
+        GameTask {
+            task_id: Uuid::new_v4(),
+            statement: statement("Echo the input"),
+            localized_statements: HashMap::from([("sv".to_string(), statement("Skriv ut"))]),
+            limits: TaskLimits::default(),
+            public_test_cases: vec![test_case(0, "public", "public")],
+            private_test_cases: vec![
+                test_case(0, "private", "private"),
+                test_case(1, PRIVATE_INPUT, PRIVATE_OUTPUT),
+            ],
+        }
+    }
+
+    fn assert_private_tests_hidden<T: Serialize>(model: &T) {
+        let json = serde_json::to_string(model).unwrap();
+        assert!(!json.contains(PRIVATE_INPUT), "private input in {}", json);
+        assert!(!json.contains(PRIVATE_OUTPUT), "private output in {}", json);
+    }
+
+    #[test]
+    fn outbound_models_do_not_contain_private_tests() {
+        let task = task();
+        for locale in [None, Some("sv")] {
+            assert_private_tests_hidden(&DefaultModel::new(GameEvent::new(TaskGameEvent {
+                task: PublicTaskView::localized(&task, locale),
+            })));
+            assert_private_tests_hidden(&DefaultModel::new(Response::new(
+                Some(TaskResponse {
+                    task: PublicTaskView::localized(&task, locale),
+                }),
+                ResponseOpCode::Task,
+            )));
+        }
+        assert_private_tests_hidden(&DefaultModel::new(GameEvent::new(TaskFinishedGameEvent {
+            task: TaskStatementView::from(&task),
+            task_index: 0,
+            player_id: 1,
+        })));
+    }
+
+    #[test]
+    fn verdicts_do_not_contain_private_tests() {
+        let task = task();
+        let outputs = [
+            // Every test passes
+            vec!["public", "private", PRIVATE_OUTPUT],
+            // The last private test fails
+            vec!["public", "private", "wrong"],
+            // The first private test fails
+            vec!["public", "wrong", PRIVATE_OUTPUT],
+        ];
+
+        for stdout in outputs {
+            let result = SandboxResponse {
+                success: true,
+                stdout: stdout.iter().map(|s| s.to_string()).collect(),
+                // Programs may echo their input on stderr
+                stderr: vec![
+                    String::new(),
+                    PRIVATE_INPUT.to_string(),
+                    PRIVATE_INPUT.to_string(),
+                ],
+                ..Default::default()
+            };
+            let response = Game::judge(&task, 0, &result);
+            assert_private_tests_hidden(&DefaultModel::new(Response::new(
+                Some(response),
+                ResponseOpCode::Compile,
+            )));
+        }
+    }
+}
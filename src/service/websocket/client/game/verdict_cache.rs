@@ -0,0 +1,79 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::models::response::compile::CompilationResponse;
+
+/// Verdicts of previous submissions keyed by their submission hash,
+/// the oldest verdict is evicted once the capacity is reached
+#[derive(Debug, Clone)]
+pub struct VerdictCache {
+    capacity: usize,
+    verdicts: HashMap<String, CompilationResponse>,
+
+    /// Submission hashes in the order they were inserted
+    order: VecDeque<String>,
+}
+
+impl VerdictCache {
+    pub fn new(capacity: usize) -> VerdictCache {
+        VerdictCache {
+            capacity,
+            verdicts: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&self, submission_hash: &str) -> Option<&CompilationResponse> {
+        self.verdicts.get(submission_hash)
+    }
+
+    pub fn insert(&mut self, submission_hash: String, response: CompilationResponse) {
+        if self
+            .verdicts
+            .insert(submission_hash.clone(), response)
+            .is_some()
+        {
+            return;
+        }
+
+        self.order.push_back(submission_hash);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.verdicts.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::service::websocket::client::game::models::response::compile::verdict::Verdict;
+
+    use super::*;
+
+    fn response(task_index: usize) -> CompilationResponse {
+        CompilationResponse::new(task_index, Verdict::Accepted, vec![])
+    }
+
+    #[test]
+    fn evicts_the_oldest_verdict() {
+        let mut cache = VerdictCache::new(2);
+        cache.insert("a".to_string(), response(0));
+        cache.insert("b".to_string(), response(1));
+        cache.insert("c".to_string(), response(2));
+
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.get("b").unwrap().task_index, 1);
+        assert_eq!(cache.get("c").unwrap().task_index, 2);
+    }
+
+    #[test]
+    fn replacing_a_verdict_does_not_evict() {
+        let mut cache = VerdictCache::new(2);
+        cache.insert("a".to_string(), response(0));
+        cache.insert("b".to_string(), response(1));
+        cache.insert("a".to_string(), response(3));
+
+        assert_eq!(cache.get("a").unwrap().task_index, 3);
+        assert!(cache.get("b").is_some());
+    }
+}
@@ -1,12 +1,15 @@
 use std::sync::Arc;
 
-use r2d2::Pool;
 use serde_json::Value;
 use uuid::Uuid;
 
 use crate::service::{
-    redis_pool::RedisConnectionManager,
-    websocket::client::{error::ClientError, game::models::Request},
+    settings::Settings,
+    state_store::Store,
+    websocket::client::{
+        error::{ClientError, ClientErrorModel},
+        game::models::Request,
+    },
     Sockets,
 };
 
@@ -21,30 +24,56 @@ impl ClientMessageHandler {
     pub async fn handle_message<'a>(
         client_id: Uuid,
         sockets: &Sockets,
-        redis_pool: Pool<RedisConnectionManager>,
+        store: Store,
         available_tasks: Arc<Vec<GameTask>>,
+        settings: Arc<Settings>,
         model: &DefaultModel<Value>,
         shard_id: &str,
     ) -> Result<(), ClientError<'a>> {
         superluminal_perf::begin_event("handle message");
-        let data = if let Some(data) = model.d.to_owned() {
-            data
-        } else {
-            return Err(ClientError::NoDataWithOpCode(
+        let mut op = None;
+        let nonce = model.nonce.clone();
+        let result = match (model.op, model.d.to_owned()) {
+            (_, None) => Err(ClientError::NoDataWithOpCode(
                 "No data was sent with opcode",
-            ));
+            )),
+            (OpCode::Request, Some(data)) => match serde_json::from_value::<Request>(data) {
+                Ok(mut request) => {
+                    op = Some(request.op);
+                    request.nonce = nonce.clone();
+                    let rate_limited = sockets
+                        .get_mut(&client_id)
+                        .map(|mut client| client.rate_limiter.check(request.op));
+                    if let Some(Err(retry_after)) = rate_limited {
+                        Err(ClientError::RateLimited(retry_after.as_millis() as u64))
+                    } else {
+                        request
+                            .handle_message(
+                                client_id,
+                                sockets,
+                                store,
+                                available_tasks,
+                                settings,
+                                shard_id,
+                            )
+                            .await
+                    }
+                }
+                Err(_) => Err(ClientError::ParsingError),
+            },
+            _ => Err(ClientError::InvalidOpCode),
         };
-
         superluminal_perf::end_event();
-        match model.op {
-            OpCode::Request => {
-                let request: Request =
-                    serde_json::from_value(data).map_err(|_| ClientError::ParsingError)?;
-                request
-                    .handle_message(client_id, sockets, redis_pool, available_tasks, shard_id)
-                    .await
+
+        // Errors are reported to the client, fatal errors before the connection is closed
+        if let Err(error) = &result {
+            if let Some(client) = sockets.get(&client_id) {
+                let _ = client
+                    .send_error(ClientErrorModel::new(error, op, nonce, error.is_fatal()))
+                    .await;
             }
-            _ => Err(ClientError::InvalidOpCode),
         }
+
+        result
     }
 }
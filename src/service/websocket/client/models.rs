@@ -1,4 +1,6 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use strum::EnumIter;
 
 pub use default::DefaultModel;
 
@@ -7,7 +9,9 @@ pub mod default;
 pub mod forced_disconnection;
 pub mod hello;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema, EnumIter,
+)]
 pub enum OpCode {
     Hello,
     Error,
@@ -1,12 +1,18 @@
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
 
 use super::{OpCode, OpCodeFetcher};
 
 // Model is to be converted into JSON when serialized before sending to clients
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DefaultModel<T> {
     pub(crate) op: OpCode,
     pub(crate) d: Option<T>,
+
+    /// Nonce of the request this message answers, only set on responses and errors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) nonce: Option<String>,
 }
 
 impl<T> DefaultModel<T> {
@@ -16,6 +22,63 @@ impl<T> DefaultModel<T> {
     {
         let op = T::op_code();
 
-        DefaultModel { op, d: Some(d) }
+        DefaultModel {
+            op,
+            d: Some(d),
+            nonce: None,
+        }
+    }
+
+    /// Tags the model with the nonce of the request it answers
+    pub fn with_nonce(mut self, nonce: Option<&str>) -> Self {
+        self.nonce = nonce.map(|nonce| nonce.to_owned());
+        self
+    }
+}
+
+impl DefaultModel<Value> {
+    /// Moves the nonce of a received request onto the envelope, so it is known
+    /// even if the request itself turns out to be invalid
+    pub fn with_request_nonce(mut self) -> Self {
+        if self.nonce.is_none() {
+            self.nonce = self
+                .d
+                .as_ref()
+                .and_then(|d| d.get("nonce").or_else(|| d.get("request_id")))
+                .and_then(Value::as_str)
+                .map(|nonce| nonce.to_owned());
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn received(model: Value) -> DefaultModel<Value> {
+        serde_json::from_value::<DefaultModel<Value>>(model)
+            .unwrap()
+            .with_request_nonce()
+    }
+
+    #[test]
+    fn request_nonce_is_read_from_the_request() {
+        let model = received(json!({ "op": "Request", "d": { "op": "Ping", "nonce": "1" } }));
+        assert_eq!(model.nonce.as_deref(), Some("1"));
+
+        let model = received(json!({ "op": "Request", "d": { "op": "Ping", "request_id": "2" } }));
+        assert_eq!(model.nonce.as_deref(), Some("2"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn request_nonce_survives_invalid_requests() {
+        let model = received(json!({ "op": "Request", "d": { "op": "Unknown", "nonce": "1" } }));
+        assert_eq!(model.nonce.as_deref(), Some("1"));
+
+        let model = received(json!({ "op": "Request", "d": { "op": "Ping" } }));
+        assert_eq!(model.nonce, None);
+    }
+}
@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 
 use super::OpCode;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ForcedDisconnection {}
 
 impl super::OpCodeFetcher for ForcedDisconnection {
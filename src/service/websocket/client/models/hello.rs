@@ -1,9 +1,10 @@
+use schemars::JsonSchema;
 use serde_derive::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use super::OpCode;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Hello {
     pub id: Uuid,
 }
@@ -0,0 +1,169 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+pub const MIN_NICKNAME_LENGTH: usize = 2;
+pub const MAX_NICKNAME_LENGTH: usize = 24;
+
+/// Reason sent to the client when a nickname is rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum NicknameRejection {
+    AlreadyIdentified,
+    TooShort,
+    TooLong,
+    InvalidCharacters,
+    Inappropriate,
+}
+
+/// Validates and normalizes nicknames sent with the Identify request
+#[derive(Debug, Clone, Default)]
+pub struct NicknameFilter {
+    /// Skeletons of all words that are not allowed anywhere in a nickname
+    denied_words: Vec<String>,
+}
+
+impl NicknameFilter {
+    pub fn new(denied_words: &[String]) -> NicknameFilter {
+        NicknameFilter {
+            denied_words: denied_words
+                .iter()
+                .map(|word| skeleton(word))
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Returns the normalized nickname if it is allowed
+    pub fn validate(&self, nickname: &str) -> Result<String, NicknameRejection> {
+        // Compatibility normalization folds full width letters, ligatures etc. into their plain form
+        let nickname = nickname
+            .nfkc()
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ");
+
+        let length = nickname.chars().count();
+        if length < MIN_NICKNAME_LENGTH {
+            return Err(NicknameRejection::TooShort);
+        } else if length > MAX_NICKNAME_LENGTH {
+            return Err(NicknameRejection::TooLong);
+        }
+
+        if !nickname
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '_' | '-' | '.'))
+        {
+            return Err(NicknameRejection::InvalidCharacters);
+        }
+
+        let nickname_skeleton = skeleton(&nickname);
+        if nickname_skeleton.is_empty() {
+            return Err(NicknameRejection::InvalidCharacters);
+        }
+
+        if self
+            .denied_words
+            .iter()
+            .any(|word| nickname_skeleton.contains(word.as_str()))
+        {
+            return Err(NicknameRejection::Inappropriate);
+        }
+
+        Ok(nickname)
+    }
+}
+
+/// Reduces a nickname to a form where visually confusable nicknames are equal,
+/// e.g. "AdMIN", "admin" and "аdmin" (with a cyrillic a) all share the same skeleton.
+/// Confusables are mapped with the UTS #39 skeleton, case and accents are ignored
+pub fn skeleton(nickname: &str) -> String {
+    let folded = fold(nickname.nfkd())
+        .filter(|c| !matches!(c, ' ' | '_' | '-' | '.'))
+        .collect::<String>();
+
+    // The UTS #39 prototypes are partly upper case, e.g. '0' maps to 'O'
+    fold(unicode_security::skeleton(&folded)).collect()
+}
+
+fn fold(chars: impl Iterator<Item = char>) -> impl Iterator<Item = char> {
+    chars
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> NicknameFilter {
+        NicknameFilter::new(&["admin".to_string()])
+    }
+
+    #[test]
+    fn validate_normalizes_whitespace_and_width() {
+        assert_eq!(filter().validate("  john   doe "), Ok("john doe".to_string()));
+        assert_eq!(filter().validate("ｊｏｈｎ"), Ok("john".to_string()));
+    }
+
+    #[test]
+    fn validate_checks_length() {
+        assert_eq!(filter().validate("a"), Err(NicknameRejection::TooShort));
+        assert_eq!(filter().validate("   "), Err(NicknameRejection::TooShort));
+        assert_eq!(
+            filter().validate(&"a".repeat(MAX_NICKNAME_LENGTH + 1)),
+            Err(NicknameRejection::TooLong)
+        );
+        assert!(filter().validate(&"a".repeat(MAX_NICKNAME_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_invalid_characters() {
+        assert_eq!(
+            filter().validate("john<script>"),
+            Err(NicknameRejection::InvalidCharacters)
+        );
+        assert_eq!(
+            filter().validate("_-."),
+            Err(NicknameRejection::InvalidCharacters)
+        );
+    }
+
+    #[test]
+    fn validate_rejects_denied_words_and_their_confusables() {
+        assert_eq!(
+            filter().validate("the_admin"),
+            Err(NicknameRejection::Inappropriate)
+        );
+        assert_eq!(
+            filter().validate("AdMIN"),
+            Err(NicknameRejection::Inappropriate)
+        );
+        assert_eq!(
+            filter().validate("аdmin"),
+            Err(NicknameRejection::Inappropriate)
+        );
+        assert_eq!(
+            filter().validate("a.d.m.i.n"),
+            Err(NicknameRejection::Inappropriate)
+        );
+        assert!(filter().validate("badminton").is_err());
+        assert!(filter().validate("administrator").is_err());
+        assert!(filter().validate("john").is_ok());
+    }
+
+    #[test]
+    fn skeleton_matches_confusables() {
+        assert_eq!(skeleton("аdmin"), skeleton("admin"));
+        assert_eq!(skeleton("AdMIN"), skeleton("admin"));
+        assert_eq!(skeleton("b0b"), skeleton("bob"));
+        assert_eq!(skeleton("jöhn"), skeleton("john"));
+        assert_eq!(skeleton("john_doe"), skeleton("john doe"));
+    }
+
+    #[test]
+    fn skeleton_keeps_distinct_names_apart() {
+        assert_ne!(skeleton("mail"), skeleton("mall"));
+        assert_ne!(skeleton("john"), skeleton("joan"));
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+use super::game::models::request::RequestOpCode;
+
+/// Allows `burst` requests at once, refilled evenly over `period`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub(crate) burst: u32,
+    pub(crate) period: Duration,
+}
+
+/// Rate limits of all limited op codes, op codes without a limit are never limited
+#[derive(Debug, Clone)]
+pub struct RateLimits {
+    limits: HashMap<RequestOpCode, RateLimit>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        let mut limits = HashMap::new();
+        limits.insert(
+            RequestOpCode::Compile,
+            RateLimit {
+                burst: 5,
+                period: Duration::from_secs(30),
+            },
+        );
+        limits.insert(
+            RequestOpCode::Run,
+            RateLimit {
+                burst: 10,
+                period: Duration::from_secs(30),
+            },
+        );
+        limits.insert(
+            RequestOpCode::Create,
+            RateLimit {
+                burst: 3,
+                period: Duration::from_secs(60),
+            },
+        );
+        limits.insert(
+            RequestOpCode::Exists,
+            RateLimit {
+                burst: 10,
+                period: Duration::from_secs(10),
+            },
+        );
+        limits.insert(
+            RequestOpCode::Join,
+            RateLimit {
+                burst: 10,
+                period: Duration::from_secs(10),
+            },
+        );
+
+        RateLimits { limits }
+    }
+}
+
+impl RateLimits {
+    /// Parses limits in the form "Compile=5/30", five requests every thirty seconds,
+    /// the parsed limits replace the default limit of the same op code
+    pub fn parse(entries: &[String]) -> Result<RateLimits, String> {
+        let mut rate_limits = RateLimits::default();
+        for entry in entries {
+            let invalid = || format!("invalid rate limit \"{}\"", entry);
+            let (op, limit) = entry.split_once('=').ok_or_else(invalid)?;
+            let (burst, period) = limit.split_once('/').ok_or_else(invalid)?;
+
+            let op: RequestOpCode = serde_json::from_value(Value::String(op.trim().to_owned()))
+                .map_err(|_| invalid())?;
+            let burst = burst.trim().parse().map_err(|_| invalid())?;
+            let period = period.trim().parse().map_err(|_| invalid())?;
+            if burst == 0 || period == 0 {
+                return Err(invalid());
+            }
+
+            rate_limits.limits.insert(
+                op,
+                RateLimit {
+                    burst,
+                    period: Duration::from_secs(period),
+                },
+            );
+        }
+
+        Ok(rate_limits)
+    }
+}
+
+/// Token bucket of a single op code
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit) -> TokenBucket {
+        TokenBucket {
+            limit,
+            tokens: limit.burst as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Takes a token, or returns how long it takes until the next token is available
+    fn take(&mut self) -> Result<(), Duration> {
+        let tokens_per_second = self.limit.burst as f64 / self.limit.period.as_secs_f64();
+        let now = Instant::now();
+        self.tokens = (self.tokens
+            + now.duration_since(self.last_refill).as_secs_f64() * tokens_per_second)
+            .min(self.limit.burst as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / tokens_per_second,
+            ))
+        }
+    }
+}
+
+/// Per connection rate limiter with one token bucket per limited op code
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    buckets: HashMap<RequestOpCode, TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(rate_limits: &RateLimits) -> RateLimiter {
+        RateLimiter {
+            buckets: rate_limits
+                .limits
+                .iter()
+                .map(|(op, limit)| (*op, TokenBucket::new(*limit)))
+                .collect(),
+        }
+    }
+
+    /// Checks if a request may be handled, returns the time to wait before retrying if not
+    pub fn check(&mut self, op: RequestOpCode) -> Result<(), Duration> {
+        match self.buckets.get_mut(&op) {
+            Some(bucket) => bucket.take(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(burst: u32, period: u64) -> RateLimit {
+        RateLimit {
+            burst,
+            period: Duration::from_secs(period),
+        }
+    }
+
+    #[test]
+    fn bucket_is_consumed_up_to_the_burst() {
+        let mut bucket = TokenBucket::new(limit(3, 30));
+        for _ in 0..3 {
+            assert_eq!(bucket.take(), Ok(()));
+        }
+
+        // One token is refilled every ten seconds
+        let retry_after = bucket.take().unwrap_err();
+        assert!(retry_after > Duration::from_secs(9) && retry_after <= Duration::from_secs(10));
+    }
+
+    #[test]
+    fn bucket_is_refilled_over_the_period() {
+        let mut bucket = TokenBucket::new(limit(3, 30));
+        for _ in 0..3 {
+            bucket.take().unwrap();
+        }
+
+        bucket.last_refill -= Duration::from_secs(10);
+        assert_eq!(bucket.take(), Ok(()));
+        assert!(bucket.take().is_err());
+
+        // The bucket never holds more than the burst
+        bucket.last_refill -= Duration::from_secs(300);
+        for _ in 0..3 {
+            assert_eq!(bucket.take(), Ok(()));
+        }
+        assert!(bucket.take().is_err());
+    }
+
+    #[test]
+    fn limiter_only_limits_op_codes_with_a_limit() {
+        let rate_limits = RateLimits::parse(&["Create=1/60".to_owned()]).unwrap();
+        let mut limiter = RateLimiter::new(&rate_limits);
+
+        assert_eq!(limiter.check(RequestOpCode::Create), Ok(()));
+        assert!(limiter.check(RequestOpCode::Create).is_err());
+        for _ in 0..100 {
+            assert_eq!(limiter.check(RequestOpCode::Ping), Ok(()));
+        }
+    }
+
+    #[test]
+    fn parse_replaces_default_limits() {
+        let rate_limits =
+            RateLimits::parse(&["Compile=2/5".to_owned(), " Ping = 1 / 1 ".to_owned()]).unwrap();
+
+        assert_eq!(rate_limits.limits[&RequestOpCode::Compile], limit(2, 5));
+        assert_eq!(rate_limits.limits[&RequestOpCode::Ping], limit(1, 1));
+        assert_eq!(rate_limits.limits[&RequestOpCode::Run], limit(10, 30));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_limits() {
+        for entry in [
+            "Compile",
+            "Compile=5",
+            "Compile=5/",
+            "Unknown=5/30",
+            "Compile=five/30",
+            "Compile=-1/30",
+            "Compile=0/30",
+            "Compile=5/0",
+        ] {
+            assert_eq!(
+                RateLimits::parse(&[entry.to_owned()]).unwrap_err(),
+                format!("invalid rate limit \"{}\"", entry)
+            );
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Maximum amount of messages waiting to be written to a single socket
+const OUTBOUND_QUEUE_SIZE: usize = 200;
+
+/// Low priority messages are dropped once fewer than this many slots are free,
+/// the remaining slots are kept for messages the client can not do without
+const LOW_PRIORITY_RESERVED_SLOTS: usize = OUTBOUND_QUEUE_SIZE / 2;
+
+/// How long a client may stay behind before it is disconnected, short bursts are absorbed
+/// by the reserved slots
+const SATURATION_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Responses and events that change the state of the client, never dropped
+    Normal,
+
+    /// Events the client can miss, e.g. the progress of other players,
+    /// dropped when the client falls behind
+    Low,
+}
+
+/// Outbound message queue of a socket, pushing never waits for the client so a slow
+/// client can not stall broadcasts to the rest of a game
+#[derive(Debug)]
+pub struct OutboundQueue {
+    shared: Arc<Shared>,
+}
+
+/// Receiving end of an outbound queue, read by the write task of the socket
+#[derive(Debug)]
+pub struct OutboundReceiver {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    grace_period: Duration,
+
+    /// Notified once a message was queued or the last queue was dropped
+    readable: Notify,
+
+    /// Notified once the client stayed behind for longer than the grace period
+    saturated: Notify,
+}
+
+#[derive(Debug)]
+struct State {
+    messages: VecDeque<Message>,
+
+    /// Amount of queues that can still push messages
+    senders: usize,
+    receiver_dropped: bool,
+
+    /// Since when the client is behind, i.e. the reserved slots are in use
+    behind_since: Option<Instant>,
+}
+
+impl OutboundQueue {
+    /// Creates a queue and the receiver the write task reads from
+    pub fn new() -> (OutboundQueue, OutboundReceiver) {
+        OutboundQueue::with_grace_period(SATURATION_GRACE_PERIOD)
+    }
+
+    fn with_grace_period(grace_period: Duration) -> (OutboundQueue, OutboundReceiver) {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                messages: VecDeque::new(),
+                senders: 1,
+                receiver_dropped: false,
+                behind_since: None,
+            }),
+            grace_period,
+            readable: Notify::new(),
+            saturated: Notify::new(),
+        });
+
+        (
+            OutboundQueue {
+                shared: shared.clone(),
+            },
+            OutboundReceiver { shared },
+        )
+    }
+
+    /// Queues a message without waiting. A client that stays behind for longer than the grace
+    /// period, or fills the whole queue, is marked as saturated
+    pub fn push(&self, message: Message, priority: Priority) -> Result<(), OutboundError> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.receiver_dropped {
+            return Err(OutboundError::Closed);
+        }
+
+        let free_slots = OUTBOUND_QUEUE_SIZE - state.messages.len();
+        if free_slots < LOW_PRIORITY_RESERVED_SLOTS {
+            let behind_since = *state.behind_since.get_or_insert_with(Instant::now);
+            if free_slots == 0 || behind_since.elapsed() >= self.shared.grace_period {
+                self.shared.saturated.notify_one();
+            }
+            if free_slots == 0 {
+                return Err(OutboundError::Saturated);
+            } else if priority == Priority::Low {
+                return Err(OutboundError::Dropped);
+            }
+        } else {
+            state.behind_since = None;
+        }
+
+        state.messages.push_back(message);
+        drop(state);
+        self.shared.readable.notify_one();
+        Ok(())
+    }
+
+    /// Completes once the queue has been saturated, the socket should be closed then
+    pub async fn saturated(&self) {
+        self.shared.saturated.notified().await
+    }
+}
+
+impl Clone for OutboundQueue {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        OutboundQueue {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for OutboundQueue {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.shared.readable.notify_one();
+        }
+    }
+}
+
+impl OutboundReceiver {
+    /// Waits for the next message, returns `None` once every queue was dropped and
+    /// all messages were received
+    pub async fn recv(&mut self) -> Option<Message> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(message) = state.messages.pop_front() {
+                    return Some(message);
+                } else if state.senders == 0 {
+                    return None;
+                }
+            }
+            self.shared.readable.notified().await;
+        }
+    }
+
+    /// Takes the next message without waiting
+    #[cfg(test)]
+    pub fn try_recv(&mut self) -> Option<Message> {
+        let mut state = self.shared.state.lock().unwrap();
+        state.messages.pop_front()
+    }
+}
+
+impl Drop for OutboundReceiver {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.receiver_dropped = true;
+        state.messages.clear();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboundError {
+    /// A low priority message was dropped because the client is falling behind
+    Dropped,
+
+    /// The queue is full, the client is disconnected
+    Saturated,
+
+    /// The socket has been closed
+    Closed,
+}
+
+impl fmt::Display for OutboundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutboundError::Dropped => write!(f, "low priority message was dropped"),
+            OutboundError::Saturated => write!(f, "outbound queue is saturated"),
+            OutboundError::Closed => write!(f, "socket is closed"),
+        }
+    }
+}
+
+impl std::error::Error for OutboundError {}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+
+    fn text(text: &str) -> Message {
+        Message::Text(text.to_owned())
+    }
+
+    /// Fills the queue up to the point where the client is behind
+    fn fall_behind(queue: &OutboundQueue) {
+        for _ in 0..=OUTBOUND_QUEUE_SIZE - LOW_PRIORITY_RESERVED_SLOTS {
+            queue.push(text("state"), Priority::Normal).unwrap();
+        }
+    }
+
+    fn is_saturated(queue: &OutboundQueue) -> bool {
+        queue.saturated().now_or_never().is_some()
+    }
+
+    #[tokio::test]
+    async fn messages_are_received_in_order() {
+        let (queue, mut receiver) = OutboundQueue::new();
+        queue.push(text("a"), Priority::Normal).unwrap();
+        queue.push(text("b"), Priority::Low).unwrap();
+
+        assert_eq!(receiver.recv().await, Some(text("a")));
+        assert_eq!(receiver.recv().await, Some(text("b")));
+
+        drop(queue);
+        assert_eq!(receiver.recv().await, None);
+    }
+
+    #[test]
+    fn dropped_receiver_closes_the_queue() {
+        let (queue, receiver) = OutboundQueue::new();
+        drop(receiver);
+
+        assert_eq!(
+            queue.push(text("a"), Priority::Normal),
+            Err(OutboundError::Closed)
+        );
+    }
+
+    #[test]
+    fn low_priority_messages_are_dropped_when_behind() {
+        let (queue, _receiver) = OutboundQueue::new();
+        fall_behind(&queue);
+
+        assert_eq!(
+            queue.push(text("chat"), Priority::Low),
+            Err(OutboundError::Dropped)
+        );
+        assert_eq!(queue.push(text("state"), Priority::Normal), Ok(()));
+    }
+
+    #[test]
+    fn falling_behind_is_tolerated_during_the_grace_period() {
+        let (queue, mut receiver) = OutboundQueue::with_grace_period(Duration::from_secs(3600));
+        fall_behind(&queue);
+        queue.push(text("state"), Priority::Normal).unwrap();
+        assert!(!is_saturated(&queue));
+
+        // Catching up resets the grace period
+        while receiver.try_recv().is_some() {}
+        queue.push(text("state"), Priority::Normal).unwrap();
+        assert!(queue.shared.state.lock().unwrap().behind_since.is_none());
+    }
+
+    #[test]
+    fn staying_behind_saturates_the_queue() {
+        let (queue, _receiver) = OutboundQueue::with_grace_period(Duration::ZERO);
+        fall_behind(&queue);
+
+        queue.push(text("state"), Priority::Normal).unwrap();
+        assert!(is_saturated(&queue));
+    }
+
+    #[test]
+    fn full_queue_saturates_immediately() {
+        let (queue, _receiver) = OutboundQueue::with_grace_period(Duration::from_secs(3600));
+        for _ in 0..OUTBOUND_QUEUE_SIZE {
+            queue.push(text("state"), Priority::Normal).unwrap();
+        }
+
+        assert_eq!(
+            queue.push(text("state"), Priority::Normal),
+            Err(OutboundError::Saturated)
+        );
+        assert!(is_saturated(&queue));
+    }
+}
@@ -0,0 +1,207 @@
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio_tungstenite::tungstenite::{http::HeaderValue, Message};
+
+/// Versions of the websocket protocol, negotiated through the Sec-WebSocket-Protocol header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// Version 1 of the protocol, messages are sent as JSON text frames
+    V1Json,
+
+    /// Version 1 of the protocol, messages are sent as flexbuffers binary frames
+    V1Flexbuffers,
+}
+
+impl Protocol {
+    /// All protocols supported by the server
+    pub const SUPPORTED: [Protocol; 2] = [Protocol::V1Json, Protocol::V1Flexbuffers];
+
+    /// Protocol used by clients that do not request one
+    pub const DEFAULT: Protocol = Protocol::V1Json;
+
+    /// Name of the protocol as sent in the Sec-WebSocket-Protocol header
+    pub fn name(&self) -> &'static str {
+        match self {
+            Protocol::V1Json => "grass.v1.json",
+            Protocol::V1Flexbuffers => "grass.v1.flexbuffers",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Protocol> {
+        Self::SUPPORTED
+            .iter()
+            .find(|protocol| protocol.name() == name)
+            .copied()
+    }
+
+    /// Picks the first protocol requested by the client that the server supports,
+    /// the header is a comma separated list ordered by the preference of the client
+    pub fn negotiate(requested: &HeaderValue) -> Option<Protocol> {
+        requested
+            .to_str()
+            .ok()?
+            .split(',')
+            .find_map(|name| Self::from_name(name.trim()))
+    }
+
+    /// Comma separated list of all supported protocols, sent to clients that are rejected
+    pub fn supported_names() -> String {
+        Self::SUPPORTED
+            .iter()
+            .map(|protocol| protocol.name())
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+
+    /// Serializes a model into a websocket message of the protocol
+    pub fn encode<T: Serialize>(&self, model: &T) -> Result<Message, CodecError> {
+        match self {
+            Protocol::V1Json => Ok(Message::Text(
+                serde_json::to_string(model).map_err(CodecError::Json)?,
+            )),
+            Protocol::V1Flexbuffers => Ok(Message::Binary(
+                flexbuffers::to_vec(model).map_err(CodecError::FlexbuffersSerialization)?,
+            )),
+        }
+    }
+
+    /// Deserializes a websocket message of the protocol,
+    /// frames of the other type (text instead of binary and vice versa) are rejected
+    pub fn decode<T: DeserializeOwned>(&self, message: &Message) -> Result<T, CodecError> {
+        match (self, message) {
+            (Protocol::V1Json, Message::Text(text)) => {
+                serde_json::from_str(text).map_err(CodecError::Json)
+            }
+            (Protocol::V1Flexbuffers, Message::Binary(data)) => {
+                flexbuffers::from_slice(data).map_err(CodecError::FlexbuffersDeserialization)
+            }
+            _ => Err(CodecError::UnexpectedFrame),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CodecError {
+    Json(serde_json::Error),
+    FlexbuffersSerialization(flexbuffers::SerializationError),
+    FlexbuffersDeserialization(flexbuffers::DeserializationError),
+
+    /// The frame type does not match the negotiated protocol
+    UnexpectedFrame,
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Json(e) => write!(f, "JSON codec error: {}", e),
+            CodecError::FlexbuffersSerialization(e) => {
+                write!(f, "flexbuffers serialization error: {}", e)
+            }
+            CodecError::FlexbuffersDeserialization(e) => {
+                write!(f, "flexbuffers deserialization error: {}", e)
+            }
+            CodecError::UnexpectedFrame => {
+                write!(f, "frame type does not match the negotiated protocol")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::service::websocket::client::{
+        game::models::{response::join::JoinResponse, Request, Response, ResponseOpCode},
+        models::{DefaultModel, OpCode},
+    };
+
+    use super::*;
+
+    fn request() -> DefaultModel<Request> {
+        serde_json::from_value(json!({
+            "op": "Request",
+            "d": {
+                "op": "Compile",
+                "d": { "task_index": 1, "code": "fn main() {\n    println!(\"hello\");\n}" },
+                "nonce": "42"
+            }
+        }))
+        .unwrap()
+    }
+
+    fn response() -> DefaultModel<Response<JoinResponse>> {
+        DefaultModel::new(Response::new(
+            Some(JoinResponse {
+                game_id: "game".to_string(),
+                is_host: false,
+                success: true,
+                nickname: Some("jöhn".to_string()),
+                player_id: Some(3),
+            }),
+            ResponseOpCode::Join,
+        ))
+        .with_nonce(Some("42"))
+    }
+
+    /// Encodes and decodes a model, the result is compared as JSON
+    fn round_trip<T: Serialize + DeserializeOwned>(protocol: Protocol, model: &T) -> Value {
+        let message = protocol.encode(model).unwrap();
+        let decoded: T = protocol.decode(&message).unwrap();
+        serde_json::to_value(decoded).unwrap()
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let expected = serde_json::to_value(request()).unwrap();
+        for protocol in Protocol::SUPPORTED {
+            assert_eq!(round_trip(protocol, &request()), expected, "{}", protocol);
+        }
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        let expected = serde_json::to_value(response()).unwrap();
+        for protocol in Protocol::SUPPORTED {
+            assert_eq!(round_trip(protocol, &response()), expected, "{}", protocol);
+        }
+    }
+
+    #[test]
+    fn requests_decode_as_untyped_models() {
+        for protocol in Protocol::SUPPORTED {
+            let message = protocol.encode(&request()).unwrap();
+            let model: DefaultModel<Value> = protocol.decode(&message).unwrap();
+            let model = model.with_request_nonce();
+
+            assert_eq!(model.op, OpCode::Request);
+            assert_eq!(model.nonce.as_deref(), Some("42"));
+            let request: Request = serde_json::from_value(model.d.unwrap()).unwrap();
+            assert_eq!(request.nonce.as_deref(), Some("42"));
+        }
+    }
+
+    #[test]
+    fn frames_of_the_other_protocol_are_rejected() {
+        let json = Protocol::V1Json.encode(&response()).unwrap();
+        let flexbuffers = Protocol::V1Flexbuffers.encode(&response()).unwrap();
+
+        assert!(matches!(
+            Protocol::V1Flexbuffers.decode::<Value>(&json),
+            Err(CodecError::UnexpectedFrame)
+        ));
+        assert!(matches!(
+            Protocol::V1Json.decode::<Value>(&flexbuffers),
+            Err(CodecError::UnexpectedFrame)
+        ));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use super::auth::AuthRejection;
+
+/// Handshakes rejected since the shard started
+pub static HANDSHAKE_REJECTIONS: HandshakeRejections = HandshakeRejections::new();
+
+/// Stage of the connection setup a client was rejected at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionReason {
+    /// The Origin header is not in the allow-list
+    Origin,
+
+    /// The bearer token is missing or invalid
+    Token,
+
+    /// None of the requested protocols are supported
+    Protocol,
+
+    /// The TLS handshake failed
+    Tls,
+
+    /// The websocket handshake was malformed or the peer went away during it
+    Handshake,
+
+    /// The hello could not be sent after the handshake
+    Hello,
+
+    /// The socket could not be registered in the global socket datastore
+    Register,
+}
+
+impl RejectionReason {
+    const COUNT: usize = 7;
+}
+
+impl From<AuthRejection> for RejectionReason {
+    fn from(rejection: AuthRejection) -> Self {
+        match rejection {
+            AuthRejection::OriginNotAllowed => RejectionReason::Origin,
+            AuthRejection::MissingToken | AuthRejection::InvalidToken => RejectionReason::Token,
+        }
+    }
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectionReason::Origin => write!(f, "origin"),
+            RejectionReason::Token => write!(f, "token"),
+            RejectionReason::Protocol => write!(f, "protocol"),
+            RejectionReason::Tls => write!(f, "tls"),
+            RejectionReason::Handshake => write!(f, "handshake"),
+            RejectionReason::Hello => write!(f, "hello"),
+            RejectionReason::Register => write!(f, "register"),
+        }
+    }
+}
+
+/// Counts rejected connections per reason
+#[derive(Debug)]
+pub struct HandshakeRejections {
+    counts: [AtomicU64; RejectionReason::COUNT],
+}
+
+impl HandshakeRejections {
+    const fn new() -> HandshakeRejections {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU64 = AtomicU64::new(0);
+        HandshakeRejections {
+            counts: [ZERO; RejectionReason::COUNT],
+        }
+    }
+
+    /// Counts a rejection, returns how many connections were rejected for the same reason so far
+    pub fn record(&self, reason: RejectionReason) -> u64 {
+        self.counts[reason as usize].fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Amount of connections rejected for a reason so far
+    #[cfg(test)]
+    pub fn count(&self, reason: RejectionReason) -> u64 {
+        self.counts[reason as usize].load(Ordering::Relaxed)
+    }
+}
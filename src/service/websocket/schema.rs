@@ -0,0 +1,172 @@
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use strum::IntoEnumIterator;
+
+use super::{
+    client::{
+        error::ClientErrorModel,
+        game::models::{
+            event::{
+                chat_message::ChatMessageGameEvent, connected_client::ConnectedClientGameEvent,
+                disconnected_client::DisconnectedClientGameEvent, shutdown::ShutdownGameEvent,
+                start::StartGameEvent, task::TaskGameEvent, task_finished::TaskFinishedGameEvent,
+                GameEvent, GameEventOpCode,
+            },
+            request::{
+                chat::ChatRequest, compile::CompileRequest, create::CreateRequest,
+                exists::ExistsRequest, identify::IdentifyRequest, join::JoinRequest,
+                leave::LeaveRequest, mute::MuteRequest, ping::PingRequest, run::RunRequest,
+                start::StartRequest, task::TaskRequest, Request, RequestOpCode,
+            },
+            response::{
+                chat::ChatResponse, compile::CompilationResponse, create::CreateResponse,
+                exists::ExistsResponse, identify::IdentifyResponse, join::JoinResponse,
+                leave::LeaveResponse, mute::MuteResponse, ping::PingResponse, run::RunResponse,
+                shutdown::ShutdownResponse, task::TaskResponse, timeout::TimeoutResponse, Response,
+                ResponseOpCode,
+            },
+        },
+        models::{forced_disconnection::ForcedDisconnection, hello::Hello, DefaultModel, OpCode},
+    },
+    protocol::Protocol,
+};
+
+/// Builds a JSON schema document describing every message of the websocket protocol,
+/// the payload of each op code is listed under the name of the op code
+pub fn protocol_schema() -> Value {
+    let mut generator = SchemaSettings::draft07().into_generator();
+
+    let messages = payloads(&mut generator, op_code_payload);
+    let requests = payloads(&mut generator, request_payload);
+    let responses = payloads(&mut generator, response_payload);
+    let events = payloads(&mut generator, game_event_payload);
+    let envelope = generator.subschema_for::<DefaultModel<Value>>();
+
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "Grass websocket protocol",
+        "protocols": Protocol::SUPPORTED.iter().map(|protocol| protocol.name()).collect::<Vec<&str>>(),
+        "envelope": envelope,
+        "messages": messages,
+        "requests": requests,
+        "responses": responses,
+        "events": events,
+        "definitions": generator.take_definitions(),
+    })
+}
+
+/// Maps every op code to the schema of its payload, keyed by the serialized name of the op code
+fn payloads<T: Serialize + IntoEnumIterator>(
+    generator: &mut SchemaGenerator,
+    payload: fn(T, &mut SchemaGenerator) -> Schema,
+) -> Map<String, Value> {
+    T::iter()
+        .map(|op| {
+            let name = match serde_json::to_value(&op) {
+                Ok(Value::String(name)) => name,
+                _ => unreachable!("op codes are serialized as strings"),
+            };
+            (name, json!(payload(op, generator)))
+        })
+        .collect()
+}
+
+fn op_code_payload(op: OpCode, generator: &mut SchemaGenerator) -> Schema {
+    match op {
+        OpCode::Hello => generator.subschema_for::<Hello>(),
+        OpCode::Error => generator.subschema_for::<ClientErrorModel>(),
+        OpCode::ForcedDisconnection => generator.subschema_for::<ForcedDisconnection>(),
+        OpCode::GameEvent => generator.subschema_for::<GameEvent<Value>>(),
+        OpCode::Request => generator.subschema_for::<Request>(),
+        OpCode::Response => generator.subschema_for::<Response<Value>>(),
+    }
+}
+
+fn request_payload(op: RequestOpCode, generator: &mut SchemaGenerator) -> Schema {
+    match op {
+        RequestOpCode::Join => generator.subschema_for::<JoinRequest>(),
+        RequestOpCode::Leave => generator.subschema_for::<LeaveRequest>(),
+        RequestOpCode::Start => generator.subschema_for::<StartRequest>(),
+        RequestOpCode::Task => generator.subschema_for::<TaskRequest>(),
+        RequestOpCode::Compile => generator.subschema_for::<CompileRequest>(),
+        RequestOpCode::Ping => generator.subschema_for::<PingRequest>(),
+        RequestOpCode::Identify => generator.subschema_for::<IdentifyRequest>(),
+        RequestOpCode::Create => generator.subschema_for::<CreateRequest>(),
+        RequestOpCode::Exists => generator.subschema_for::<ExistsRequest>(),
+        RequestOpCode::Chat => generator.subschema_for::<ChatRequest>(),
+        RequestOpCode::Mute => generator.subschema_for::<MuteRequest>(),
+        RequestOpCode::Run => generator.subschema_for::<RunRequest>(),
+    }
+}
+
+fn response_payload(op: ResponseOpCode, generator: &mut SchemaGenerator) -> Schema {
+    match op {
+        ResponseOpCode::Join => generator.subschema_for::<JoinResponse>(),
+        ResponseOpCode::Leave => generator.subschema_for::<LeaveResponse>(),
+        ResponseOpCode::Shutdown => generator.subschema_for::<ShutdownResponse>(),
+        ResponseOpCode::Task => generator.subschema_for::<TaskResponse>(),
+        ResponseOpCode::Timeout => generator.subschema_for::<TimeoutResponse>(),
+        ResponseOpCode::Ping => generator.subschema_for::<PingResponse>(),
+        ResponseOpCode::Compile => generator.subschema_for::<CompilationResponse>(),
+        ResponseOpCode::Identify => generator.subschema_for::<IdentifyResponse>(),
+        ResponseOpCode::Create => generator.subschema_for::<CreateResponse>(),
+        ResponseOpCode::Exists => generator.subschema_for::<ExistsResponse>(),
+        ResponseOpCode::Mute => generator.subschema_for::<MuteResponse>(),
+        ResponseOpCode::Run => generator.subschema_for::<RunResponse>(),
+        ResponseOpCode::Chat => generator.subschema_for::<ChatResponse>(),
+    }
+}
+
+fn game_event_payload(op: GameEventOpCode, generator: &mut SchemaGenerator) -> Schema {
+    match op {
+        GameEventOpCode::Shutdown => generator.subschema_for::<ShutdownGameEvent>(),
+        GameEventOpCode::Start => generator.subschema_for::<StartGameEvent>(),
+        GameEventOpCode::Task => generator.subschema_for::<TaskGameEvent>(),
+        GameEventOpCode::TaskFinished => generator.subschema_for::<TaskFinishedGameEvent>(),
+        GameEventOpCode::ConnectedClient => generator.subschema_for::<ConnectedClientGameEvent>(),
+        GameEventOpCode::DisconnectedClient => {
+            generator.subschema_for::<DisconnectedClientGameEvent>()
+        }
+        GameEventOpCode::ChatMessage => generator.subschema_for::<ChatMessageGameEvent>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checked_in_schema_is_up_to_date() {
+        let checked_in: Value =
+            serde_json::from_str(include_str!("../../../protocol.schema.json")).unwrap();
+        assert!(
+            checked_in == protocol_schema(),
+            "protocol.schema.json is outdated, regenerate it with `cargo run -- --dump-schema > protocol.schema.json`"
+        );
+    }
+
+    #[test]
+    fn schema_lists_every_op_code() {
+        let schema = protocol_schema();
+        assert_eq!(
+            schema["messages"].as_object().unwrap().len(),
+            OpCode::iter().count()
+        );
+        assert_eq!(
+            schema["requests"].as_object().unwrap().len(),
+            RequestOpCode::iter().count()
+        );
+        assert_eq!(
+            schema["responses"].as_object().unwrap().len(),
+            ResponseOpCode::iter().count()
+        );
+        assert_eq!(
+            schema["events"].as_object().unwrap().len(),
+            GameEventOpCode::iter().count()
+        );
+    }
+}
@@ -0,0 +1,127 @@
+//! Boots a shard that keeps its state in memory and plays through creating and joining a game
+
+use std::{
+    net::TcpListener,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Shard process, killed when the test ends
+struct Shard {
+    process: Child,
+    port: u16,
+}
+
+impl Shard {
+    fn start() -> Shard {
+        // Reserve a free port, the shard binds it right after it is released
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        // No redis server runs during the tests, resetting it would fail
+        let process = Command::new(env!("CARGO_BIN_EXE_grass"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .env("ADDRESS", "127.0.0.1")
+            .env("PORT", port.to_string())
+            .env("IN_MEMORY_STATE", "true")
+            .env("SHOULD_RESET_REDIS", "true")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("could not start the shard");
+
+        Shard { process, port }
+    }
+
+    async fn connect(&mut self) -> Socket {
+        for _ in 0..100 {
+            if let Ok((socket, _)) =
+                tokio_tungstenite::connect_async(format!("ws://127.0.0.1:{}/", self.port)).await
+            {
+                return socket;
+            }
+
+            if let Some(status) = self.process.try_wait().unwrap() {
+                panic!("shard exited with {}", status);
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        panic!("shard did not start listening");
+    }
+}
+
+impl Drop for Shard {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Reads the next JSON message, pings are skipped
+async fn receive(socket: &mut Socket) -> Value {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("shard did not answer")
+            .expect("socket was closed")
+            .unwrap();
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+/// Sends a request and returns the data of the response with the same nonce
+async fn request(socket: &mut Socket, op: &str, d: Value) -> Value {
+    let nonce = format!("{}-nonce", op);
+    let request = json!({ "op": "Request", "d": { "op": op, "d": d, "nonce": nonce } });
+    socket
+        .send(Message::Text(request.to_string()))
+        .await
+        .unwrap();
+
+    loop {
+        let message = receive(socket).await;
+        if message["nonce"] == nonce.as_str() {
+            assert_eq!(message["op"], "Response", "request failed: {}", message);
+            return message["d"]["d"].clone();
+        }
+    }
+}
+
+#[tokio::test]
+async fn shard_runs_without_redis() {
+    let mut shard = Shard::start();
+
+    let mut host = shard.connect().await;
+    assert_eq!(receive(&mut host).await["op"], "Hello");
+    request(&mut host, "Identify", json!({ "nickname": "host" })).await;
+    let created = request(&mut host, "Create", json!({})).await;
+    let game_id = created["game_id"].as_str().unwrap().to_owned();
+
+    let exists = request(&mut host, "Exists", json!({ "game_id": game_id })).await;
+    assert_eq!(exists["exists"], true);
+
+    let joined = request(&mut host, "Join", json!({ "game_id": game_id })).await;
+    assert_eq!(joined["success"], true);
+    assert_eq!(joined["is_host"], true);
+
+    let mut player = shard.connect().await;
+    assert_eq!(receive(&mut player).await["op"], "Hello");
+    request(&mut player, "Identify", json!({ "nickname": "player" })).await;
+    let joined = request(&mut player, "Join", json!({ "game_id": game_id })).await;
+    assert_eq!(joined["success"], true);
+    assert_eq!(joined["is_host"], false);
+    assert_eq!(joined["player_id"], 1);
+}